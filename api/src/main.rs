@@ -1,62 +1,227 @@
-use warp::Filter;
+use warp::{Filter, Rejection, Reply, http::StatusCode, reply::{with_header, with_status, Response}};
+use std::convert::Infallible;
 use dotenv::dotenv;
 use std::env;
+use std::collections::HashMap;
 use lazy_static::lazy_static;
 use std::sync::Mutex;
+use uuid::Uuid;
 use bex::bdd::BddBase;
 use bex::nid::NID;
 use bex::base::Base;
 
 lazy_static! {
-    pub static ref BDD_BASE: Mutex<BddBase> = Mutex::new(BddBase::new());
+    pub static ref BDD_BASE: Mutex<BddBase> = Mutex::new(new_base());
+    /// separate bases for clients that don't want to share the global one.
+    pub static ref SESSIONS: Mutex<HashMap<Uuid, Mutex<BddBase>>> = Mutex::new(HashMap::new());
 }
 
+/// number of worker threads for each base, from `BEX_THREADS`. (0 means one per cpu.)
+fn thread_count() -> usize {
+    env::var("BEX_THREADS").ok().and_then(|s| s.parse().ok()).unwrap_or(4)}
+
+/// create a new base with the configured number of threads.
+fn new_base() -> BddBase { BddBase::new_with_threads(thread_count()) }
+
+/// the operations a client can perform on a base.
+enum Op {
+    Ite(NID, NID, NID),
+    Xor(NID, NID),
+    And(NID, NID),
+    Or(NID, NID),
+    Nid(NID),
+    Dot(NID),
+    Count(NID, String),
+    Save(String),
+    Load(String)}
+
+impl Op {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Op::Dot(_) => "text/vnd.graphviz",
+            _ => "text/plain; charset=utf-8" }}}
+
+/// filter that parses the path of a request into an `Op`.
+fn ops() -> impl Filter<Extract=(Op,), Error=Rejection> + Clone {
+    let ite = warp::path!("ite" / NID / NID / NID).map(Op::Ite);
+    let xor = warp::path!("xor" / NID / NID).map(Op::Xor);
+    let and = warp::path!("and" / NID / NID).map(Op::And);
+    let or = warp::path!("or" / NID / NID).map(Op::Or);
+    let nid = warp::path!("nid" / NID).map(Op::Nid);
+    let dot = warp::path!("dot" / NID).map(Op::Dot);
+    // nvars is validated in run(), so a bad number gets a 400 instead of a 404.
+    let count = warp::path!("count" / NID / String).map(Op::Count);
+    ite.or(xor).unify().or(and).unify().or(or).unify().or(nid).unify()
+        .or(dot).unify().or(count).unify()}
+
+/// filter for the operations that read or write files (these are POST requests).
+fn file_ops() -> impl Filter<Extract=(Op,), Error=Rejection> + Clone {
+    let save = warp::path!("save" / String).map(Op::Save);
+    let load = warp::path!("load" / String).map(Op::Load);
+    save.or(load).unify()}
+
+/// path to a saved base. Files live in the directory named by `BEX_FILES` (default: ".").
+fn file_path(name: &str) -> Result<String, String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("bad file name: '{name}'")) }
+    let dir = env::var("BEX_FILES").unwrap_or_else(|_| ".".to_string());
+    Ok(std::path::Path::new(&dir).join(format!("{name}.bdd")).to_string_lossy().to_string())}
+
+/// perform an operation on the given base. Errors are for malformed requests.
+fn run(base: &mut BddBase, op: Op) -> Result<String, String> {
+    Ok(match op {
+        Op::Ite(i, t, e) => format!("{}", base.ite(i, t, e)),
+        Op::Xor(x, y) => format!("{}", base.xor(x, y)),
+        Op::And(x, y) => format!("{}", base.and(x, y)),
+        Op::Or(x, y) => format!("{}", base.or(x, y)),
+        Op::Nid(nid) =>
+            if nid.is_lit() || nid.is_const() || nid.is_fun() { format!("{nid}") }
+            else {
+                let (v, hi, lo) = base.get_vhl(nid);
+                format!("v: {v} hi: {hi} lo: {lo}") }
+        Op::Dot(nid) => { let mut s = String::new(); base.dot(nid, &mut s); s }
+        Op::Count(nid, nvars) => {
+            let nvars: usize = nvars.parse().map_err(|_| format!("nvars should be a number, not '{nvars}'"))?;
+            // (solution_count asserts these, and a panic here would poison the base's mutex)
+            if nvars >= 64 { return Err(format!("nvars should be less than 64, not {nvars}")) }
+            let needed = if nid.is_const() { 0 }
+                else if nid.vid().is_var() { nid.vid().var_ix() + 1 }
+                else { return Err(format!("can't count solutions for {nid}")) };
+            if nvars < needed { return Err(format!("{nid} depends on {needed} variables, but nvars is {nvars}")) }
+            format!("{}", base.solution_count(nid, nvars)) }
+        Op::Save(name) => {
+            base.save(&file_path(&name)?).map_err(|e| format!("couldn't save '{name}': {e}"))?;
+            format!("saved {name}") }
+        Op::Load(name) => {
+            // replace the whole base, and report the new node count
+            *base = BddBase::load(&file_path(&name)?).map_err(|e| format!("couldn't load '{name}': {e}"))?;
+            format!("{}", base.len()) }})}
+
+/// perform an operation on the given base, and wrap the result in a response.
+fn respond(base: &mut BddBase, op: Op) -> Response {
+    let ct = op.content_type();
+    match run(base, op) {
+        Ok(body) => with_header(body, "content-type", ct).into_response(),
+        Err(msg) => with_status(msg, StatusCode::BAD_REQUEST).into_response() }}
+
+/// run an operation against a session's base (or reply with 404 if there's no such session).
+fn run_in_session(id: Uuid, op: Op) -> Response {
+    let sessions = SESSIONS.lock().unwrap();
+    match sessions.get(&id) {
+        Some(base) => respond(&mut base.lock().unwrap(), op),
+        None => with_status(format!("no such session: {id}"), StatusCode::NOT_FOUND).into_response() }}
+
+fn routes() -> impl Filter<Extract=(impl warp::Reply,), Error=Rejection> + Clone {
+    let version = env!("CARGO_PKG_VERSION");
+    let hello = warp::path::end().map(move || format!("bex-api version: {}", version));
+
+    let global = warp::get().and(ops()).or(warp::post().and(file_ops())).unify()
+        .map(|op| respond(&mut BDD_BASE.lock().unwrap(), op));
+
+    let new_session = warp::post().and(warp::path!("session")).map(|| {
+        let id = Uuid::new_v4();
+        SESSIONS.lock().unwrap().insert(id, Mutex::new(new_base()));
+        id.to_string()});
+
+    let session = warp::path("session").and(warp::path::param::<Uuid>())
+        .and(warp::get().and(ops()).or(warp::post().and(file_ops())).unify())
+        .and_then(|id, op| async move { Ok::<_, Infallible>(run_in_session(id, op)) });
+
+    hello.or(global).or(new_session).or(session)}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    println!("Using {} worker threads per base", BDD_BASE.lock().unwrap().thread_count());
     let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port = env::var("PORT").unwrap_or_else(|_| "3030".to_string()).parse().expect("PORT must be a number");
 
-    let version = env!("CARGO_PKG_VERSION");
-    let hello = warp::path::end().map(move || format!("bex-api version: {}", version));
-
-    let vhl = warp::path!("ite" / NID / NID / NID)
-        .map(|vid: NID, nid1: NID, nid2: NID| {
-            let mut bdd_base = BDD_BASE.lock().unwrap();
-            let new_nid = bdd_base.ite(vid, nid1, nid2);
-            format!("{new_nid}")});
-
-    let xor = warp::path!("xor" / NID / NID)
-        .map(|nid1: NID, nid2: NID| {
-            let mut bdd_base = BDD_BASE.lock().unwrap();
-            let new_nid = bdd_base.xor(nid1, nid2);
-            format!("{new_nid}")});
-
-    let and = warp::path!("and" / NID / NID)
-        .map(|nid1: NID, nid2: NID| {
-            let mut bdd_base = BDD_BASE.lock().unwrap();
-            let new_nid = bdd_base.and(nid1, nid2);
-            format!("{new_nid}")});
-
-    let or = warp::path!("or" / NID / NID)
-        .map(|nid1: NID, nid2: NID| {
-            let mut bdd_base = BDD_BASE.lock().unwrap();
-            let new_nid = bdd_base.or(nid1, nid2);
-            format!("{new_nid}")});
-
-    let nid = warp::path!("nid" / NID)
-            .map(|nid: NID| {
-                if nid.is_lit() || nid.is_const() || nid.is_fun() { format!("{nid}") }
-                else {
-                    let bdd_base = BDD_BASE.lock().unwrap();
-                    let (v, hi, lo) = bdd_base.get_vhl(nid);
-                    format!("v: {v} hi: {hi} lo: {lo}") }});
-
-    let routes = hello.or(vhl).or(xor).or(and).or(or).or(nid);
-
     let addr = (host.parse::<std::net::IpAddr>().expect("HOST must be a valid IP address"), port);
 
     println!("Server listening on http://{}:{}", host, port);
 
-    warp::serve(routes).run(addr).await;
+    warp::serve(routes()).run(addr).await;
 }
+
+
+// -- test suite --
+
+#[cfg(test)]
+async fn get(path: &str) -> (u16, String) {
+    let res = warp::test::request().path(path).reply(&routes()).await;
+    (res.status().as_u16(), String::from_utf8(res.body().to_vec()).unwrap()) }
+
+#[cfg(test)]
+async fn post(path: &str) -> (u16, String) {
+    let res = warp::test::request().method("POST").path(path).reply(&routes()).await;
+    (res.status().as_u16(), String::from_utf8(res.body().to_vec()).unwrap()) }
+
+#[tokio::test]
+async fn test_sessions() {
+    let (_, s1) = post("/session").await;
+    let (_, s2) = post("/session").await;
+    assert_ne!(s1, s2);
+    // the first node in each session gets the same nid...
+    let (_, n1) = get(&format!("/session/{s1}/and/x0/x1")).await;
+    let (_, n2) = get(&format!("/session/{s2}/xor/x0/x1")).await;
+    assert_eq!(n1, n2);
+    // ... but it refers to a different node in each base:
+    let (_, v1) = get(&format!("/session/{s1}/nid/{n1}")).await;
+    let (_, v2) = get(&format!("/session/{s2}/nid/{n2}")).await;
+    assert_eq!(v1, "v: x1 hi: x0 lo: O");
+    assert_eq!(v2, "v: x1 hi: !x0 lo: x0");
+    let (status, _) = get(&format!("/session/{}/and/x0/x1", Uuid::new_v4())).await;
+    assert_eq!(status, 404, "unknown sessions should be rejected"); }
+
+#[test]
+fn test_thread_count() {
+    env::set_var("BEX_THREADS", "1");
+    assert_eq!(new_base().thread_count(), 1);
+    env::remove_var("BEX_THREADS");
+    assert_eq!(thread_count(), 4); }
+
+#[tokio::test]
+async fn test_dot() {
+    let (_, n) = get("/and/x0/x1").await;
+    let res = warp::test::request().path(&format!("/dot/{n}")).reply(&routes()).await;
+    assert_eq!(res.headers()["content-type"], "text/vnd.graphviz");
+    let body = String::from_utf8(res.body().to_vec()).unwrap();
+    assert!(body.contains("digraph bdd"));
+    assert!(body.contains(&format!("\"{n}\"")), "graph should include the node itself");
+    for lit in ["O", "I", "x0", "!x1"] {
+        let (_, body) = get(&format!("/dot/{lit}")).await;
+        assert!(body.contains("digraph bdd"), "/dot/{lit} should still be a graph"); }}
+
+#[tokio::test]
+async fn test_count() {
+    let (_, n) = get("/or/x0/x1").await;
+    assert_eq!(get(&format!("/count/{n}/2")).await, (200, "3".to_string()));
+    assert_eq!(get(&format!("/count/{n}/3")).await, (200, "6".to_string()));
+    let (status, _) = get(&format!("/count/{n}/two")).await;
+    assert_eq!(status, 400);
+    // nvars has to cover the nid's variables, and stay under 64:
+    assert_eq!(get("/count/x5/2").await.0, 400);
+    assert_eq!(get("/count/x5/6").await, (200, "32".to_string()));
+    assert_eq!(get("/count/x0/64").await.0, 400);
+    assert_eq!(get("/count/x0/63").await.0, 200);
+    // ... and neither of those should have broken the global base:
+    assert_eq!(get(&format!("/count/{n}/2")).await, (200, "3".to_string())); }
+
+#[tokio::test]
+async fn test_save_load() {
+    env::set_var("BEX_FILES", env::temp_dir());
+    // (use sessions here, so loading doesn't disturb the other tests)
+    let (_, s1) = post("/session").await;
+    let (_, n) = get(&format!("/session/{s1}/and/x0/x1")).await;
+    let (_, n) = get(&format!("/session/{s1}/xor/{n}/x2")).await;
+    let (_, before) = get(&format!("/session/{s1}/nid/{n}")).await;
+    let name = format!("bex-api-test-{s1}");
+    assert_eq!(post(&format!("/session/{s1}/save/{name}")).await.0, 200);
+    let (_, s2) = post("/session").await;
+    let (status, count) = post(&format!("/session/{s2}/load/{name}")).await;
+    assert_eq!(status, 200);
+    assert!(count.parse::<usize>().unwrap() > 0, "load should report the node count");
+    assert_eq!(get(&format!("/session/{s2}/nid/{n}")).await, (200, before));
+    std::fs::remove_file(file_path(&name).unwrap()).unwrap();
+    assert_eq!(post(&format!("/session/{s2}/load/{name}")).await.0, 400);
+    assert_eq!(post(&format!("/session/{s2}/save/..")).await.0, 400); }
@@ -2,7 +2,6 @@
 /// while it's running. I wrote it to debug VhlSwarm and figure out how
 /// to send messages to it while it was in a separate thread. (The answer
 /// was to expose q_sender and poll that channel in swarm::run())
-
 use std::io;
 use std::io::Write;
 use std::thread;
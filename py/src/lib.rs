@@ -1,53 +1,122 @@
 //! wrap bex as a python module
 extern crate bex;
 use pyo3::prelude::*;
-use pyo3::PyObjectProtocol;
+use pyo3::{PyObjectProtocol, PyNumberProtocol};
 use pyo3::exceptions::PyException;
-use bex::{Base, GraphViz, ast::ASTBase, nid::{I,O,NID}, vid::VID};
+use bex::{Base, GraphViz, ast::ASTBase, bdd::BddBase, nid::{I,O,NID}, vid::VID};
+use bex::{solve::{solve, SubSolver}, swap::SwapSolver};
 
-#[pyclass(name="NID")] struct PyNID{ nid:NID }
+/// A node id. NIDs produced by an `AST` remember which base they came from,
+/// so python code can combine them with `&`, `|`, `^` and `~`. Binary operators
+/// need at least one operand with a base (and both bases must match if present).
+/// Plain nids like `var(0)` have no base: use `AST.var(0)` to get a bound one.
+#[pyclass(name="NID")] struct PyNID{ nid:NID, base:Option<Py<PyAST>> }
 #[pyclass(name="VID")] struct PyVID{ vid:VID }
 #[pyclass(name="AST")] struct PyAST { base: ASTBase }
+#[pyclass(name="BDD")] struct PyBDD { base: BddBase }
 
-enum BexErr { NegVar, NegVir }
+enum BexErr { NegVar, NegVir, NoBase, MixedBase }
 impl std::convert::From<BexErr> for PyErr {
   fn from(err: BexErr) -> PyErr {
     match err {
       BexErr::NegVar => PyException::new_err("var(i) expects i >= 0"),
-      BexErr::NegVir => PyException::new_err("vir(i) expects i >= 0") }}}
+      BexErr::NegVir => PyException::new_err("vir(i) expects i >= 0"),
+      BexErr::NoBase => PyException::new_err("operator needs a nid that belongs to an AST"),
+      BexErr::MixedBase => PyException::new_err("can't combine nids from different bases") }}}
+
+impl PyNID {
+  fn new(nid:NID)->Self { PyNID{ nid, base:None }}
+
+  /// apply a binary operator in whichever base the operands belong to.
+  fn binop(lhs:PyRef<PyNID>, rhs:PyRef<PyNID>, op:fn(&mut ASTBase, NID, NID)->NID)->PyResult<PyNID> {
+    let py = lhs.py();
+    let base = match (&lhs.base, &rhs.base) {
+      (Some(x), Some(y)) if x.as_ptr() != y.as_ptr() => return Err(BexErr::MixedBase.into()),
+      (Some(x), _) | (None, Some(x)) => x.clone_ref(py),
+      (None, None) => return Err(BexErr::NoBase.into()) };
+    let nid = op(&mut base.as_ref(py).borrow_mut().base, lhs.nid, rhs.nid);
+    Ok(PyNID{ nid, base:Some(base) }) }}
 
 #[pymethods]
 impl PyNID {
   #[staticmethod]
-  fn var(i:i32)->PyResult<Self> { if i<0 { Err(BexErr::NegVar.into()) } else { Ok(PyNID{ nid:NID::var(i as u32)}) }}
+  fn var(i:i32)->PyResult<Self> { if i<0 { Err(BexErr::NegVar.into()) } else { Ok(PyNID::new(NID::var(i as u32))) }}
   #[staticmethod]
-  fn vir(i:i32)->PyResult<Self> { if i<0 { Err(BexErr::NegVir.into()) } else { Ok(PyNID{ nid:NID::vir(i as u32)}) }}}
+  fn vir(i:i32)->PyResult<Self> { if i<0 { Err(BexErr::NegVir.into()) } else { Ok(PyNID::new(NID::vir(i as u32))) }}}
 
 #[pyproto]
 impl PyObjectProtocol for PyNID {
   fn __str__(&self) -> String { self.nid.to_string() }
   fn __repr__(&self) -> String { format!("<NID({:?})>", self.nid) }}
 
+#[pyproto]
+impl PyNumberProtocol for PyNID {
+  fn __and__(lhs:PyRef<PyNID>, rhs:PyRef<PyNID>)->PyResult<PyNID> { PyNID::binop(lhs, rhs, ASTBase::and) }
+  fn __or__(lhs:PyRef<PyNID>, rhs:PyRef<PyNID>)->PyResult<PyNID>  { PyNID::binop(lhs, rhs, ASTBase::or) }
+  fn __xor__(lhs:PyRef<PyNID>, rhs:PyRef<PyNID>)->PyResult<PyNID> { PyNID::binop(lhs, rhs, ASTBase::xor) }
+  fn __invert__(&self)->PyNID {
+    let base = Python::with_gil(|py| self.base.as_ref().map(|b| b.clone_ref(py)));
+    PyNID{ nid:!self.nid, base }}}
+
 #[pyproto]
 impl PyObjectProtocol for PyVID {
   fn __str__(&self) -> String { self.vid.to_string() }
   fn __repr__(&self) -> String { format!("<VID({:?})>", self.vid) }}
 
+impl PyAST {
+  /// wrap a nid so that it remembers this base.
+  fn bind(slf:&PyCell<Self>, nid:NID)->PyNID { PyNID{ nid, base:Some(slf.into()) }}}
+
 #[pymethods]
 impl PyAST {
   #[new] fn __new__()->Self { Self{ base: ASTBase::empty() }}
-  fn op_and(&mut self, x:&PyNID, y:&PyNID)->PyNID { PyNID{ nid:self.base.and(x.nid, y.nid) }}
-  fn op_xor(&mut self, x:&PyNID, y:&PyNID)->PyNID { PyNID{ nid:self.base.xor(x.nid, y.nid) }}
-  fn op_or(&mut self, x:&PyNID, y:&PyNID)->PyNID  { PyNID{ nid:self.base.or(x.nid, y.nid) }}
+  /// input variable i, bound to this base (so it works with the python operators)
+  fn var(slf:&PyCell<Self>, i:i32)->PyResult<PyNID> { Ok(PyAST::bind(slf, PyNID::var(i)?.nid)) }
+  fn op_and(slf:&PyCell<Self>, x:&PyNID, y:&PyNID)->PyNID { let n = slf.borrow_mut().base.and(x.nid, y.nid); PyAST::bind(slf, n) }
+  fn op_xor(slf:&PyCell<Self>, x:&PyNID, y:&PyNID)->PyNID { let n = slf.borrow_mut().base.xor(x.nid, y.nid); PyAST::bind(slf, n) }
+  fn op_or(slf:&PyCell<Self>, x:&PyNID, y:&PyNID)->PyNID  { let n = slf.borrow_mut().base.or(x.nid, y.nid); PyAST::bind(slf, n) }
+  /// solve the expression by substituting it into a fresh BDD.
+  /// returns the BDD and the nid of the answer inside it.
+  fn solve_bdd(&self, top:&PyNID)->(PyBDD, PyNID) {
+    let mut bdd = BddBase::new();
+    let res = solve(&mut bdd, self.base.raw_ast(), top.nid);
+    (PyBDD{ base:bdd }, PyNID::new(res.n)) }
+  /// solve the expression with the swap solver, and return all solutions
+  /// over the first nvars inputs, as a sorted list of ints (bit i = value of x_i)
+  fn solve_swap(&self, top:&PyNID, nvars:usize)->Vec<usize> {
+    let mut swap = SwapSolver::new();
+    let res = solve(&mut swap, self.base.raw_ast(), top.nid);
+    let mut sols:Vec<usize> = swap.get_all(res.n, nvars).iter().map(|r| r.as_usize()).collect();
+    sols.sort(); sols }
   fn to_dot(&self, x:&PyNID)->String { let mut s = String::new(); self.base.write_dot(x.nid, &mut s); s }}
 
+#[pymethods]
+impl PyBDD {
+  #[new] fn __new__()->Self { Self{ base: BddBase::new() }}
+  fn op_and(&mut self, x:&PyNID, y:&PyNID)->PyNID { PyNID::new(self.base.and(x.nid, y.nid)) }
+  fn op_xor(&mut self, x:&PyNID, y:&PyNID)->PyNID { PyNID::new(self.base.xor(x.nid, y.nid)) }
+  fn op_or(&mut self, x:&PyNID, y:&PyNID)->PyNID  { PyNID::new(self.base.or(x.nid, y.nid)) }
+  fn ite(&mut self, f:&PyNID, g:&PyNID, h:&PyNID)->PyNID { PyNID::new(self.base.ite(f.nid, g.nid, h.nid)) }
+  fn to_dot(&self, x:&PyNID)->String { let mut s = String::new(); self.base.write_dot(x.nid, &mut s); s }
+  /// number of distinct nodes reachable from x
+  fn node_count(&self, x:&PyNID)->usize { self.base.node_count(x.nid) }
+  /// number of solutions over the first nvars input variables
+  fn solution_count(&self, x:&PyNID, nvars:usize)->u64 { self.base.solution_count(x.nid, nvars) }
+  /// list of solutions, each encoded as an int (bit i = value of x_i)
+  fn solutions(&self, x:&PyNID, nvars:usize)->Vec<usize> {
+    self.base.solutions_pad(x.nid, nvars).map(|r| r.as_usize()).collect() }
+  /// one solution (as an int), or None if there are no solutions
+  fn sat_one(&self, x:&PyNID, nvars:usize)->Option<usize> {
+    self.base.solutions_pad(x.nid, nvars).next().map(|r| r.as_usize()) }}
+
 #[pymodule]
 fn bex(py:Python, m:&PyModule)->PyResult<()> {
   m.add_class::<PyVID>()?;
   m.add_class::<PyNID>()?;
   m.add_class::<PyAST>()?;
-  m.setattr("O", PyNID{nid:O}.into_py(py))?;
-  m.setattr("I", PyNID{nid:I}.into_py(py))?;
+  m.add_class::<PyBDD>()?;
+  m.setattr("O", PyNID::new(O).into_py(py))?;
+  m.setattr("I", PyNID::new(I).into_py(py))?;
 
   #[pyfn(m, "var")] fn var(_py:Python, i:i32)->PyResult<PyNID> { PyNID::var(i) }
   #[pyfn(m, "vir")] fn vir(_py:Python, i:i32)->PyResult<PyNID> { PyNID::vir(i) }
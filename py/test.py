@@ -12,3 +12,10 @@ n1 = base.op_or(x2, n0)
 
 dot = base.to_dot(n1)
 print("(x0 & x1) | x2 :\n\n", dot)
+
+# operators on nids bound to a base:
+x, y = base.var(0), base.var(1)
+n = ~(x & y)
+assert str(n) == '!' + str(base.op_and(x, y))
+assert str(x ^ y) == str(base.op_xor(x, y))
+assert str((x | y) & var(2)) == str(base.op_and(base.op_or(x, y), var(2)))
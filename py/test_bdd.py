@@ -0,0 +1,21 @@
+from bex import var, O, BDD
+
+def test_ops():
+    x0, x1, x2 = var(0), var(1), var(2)
+    base = BDD()
+    n = base.op_and(x0, x1)
+    assert base.solution_count(n, 2) == 1
+    assert base.node_count(n) == 2
+    assert base.solution_count(base.op_or(x0, x1), 2) == 3
+    assert base.solution_count(base.op_xor(x0, x1), 2) == 2
+    assert base.solution_count(base.ite(x2, x0, x1), 3) == 4
+    assert base.solution_count(base.op_and(x0, O), 2) == 0
+    assert 'digraph' in base.to_dot(n)
+
+def test_solutions():
+    x0, x1 = var(0), var(1)
+    base = BDD()
+    n = base.op_and(x0, x1)
+    assert base.solution_count(n, 2) == 1
+    assert base.solutions(n, 2) == [0b11]
+    assert base.sat_one(n, 2) == 0b11
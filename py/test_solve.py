@@ -0,0 +1,25 @@
+from bex import AST
+
+def factor_6():
+    """build the AST for (x < y) & (x * y == 6), where x and y are bitpairs.
+    x is (x0, x1) and y is (x2, x3), so the only answer (2,3) is 0b1110."""
+    base = AST()
+    x0, x1, y0, y1 = [base.var(i) for i in range(4)]
+    # 2x2 bit multiplication:
+    c1 = (x1 & y0) & (x0 & y1)
+    p0 = x0 & y0
+    p1 = (x1 & y0) ^ (x0 & y1)
+    p2 = (x1 & y1) ^ c1
+    p3 = (x1 & y1) & c1
+    eq6 = (~p0 & p1) & (p2 & ~p3)
+    lt = (~x1 & y1) | (~(x1 ^ y1) & (~x0 & y0))
+    return base, lt & eq6
+
+def test_solve_bdd():
+    base, top = factor_6()
+    bdd, n = base.solve_bdd(top)
+    assert bdd.solutions(n, 4) == [0b1110]
+
+def test_solve_swap():
+    base, top = factor_6()
+    assert base.solve_swap(top, 4) == [0b1110]
@@ -13,16 +13,26 @@
 //! ```
 //! In addition, identical suffixes after factoring always refer to the same node.
 use std::collections::{HashMap, HashSet};
+use std::io;
+use serde::{Serialize, Deserialize};
 use crate::base::Base;
+use crate::io::{write_versioned, read_versioned};
 use crate::simp;
 use crate::{nid, nid::{NID,I,O}};
 use crate::vid::{VID,VidOrdering};
 use crate::cur::{Cursor, CursorPlan};
 use crate::reg::Reg;
 use crate::vhl::{Vhl, HiLo, HiLoBase, Walkable};
-use crate::bdd::BddBase; // for solutions
 #[cfg(test)] use crate::vid::{topmost, botmost};
+#[cfg(test)] use crate::bdd::BddBase;
 
+/// format version for files written by `ANFBase::save`. (see `io::write_versioned`)
+pub const ANF_FORMAT_VERSION:u32 = 1;
+
+/// The part of an ANFBase that gets written to disk. (The cache is rebuilt on load.)
+#[derive(Serialize, Deserialize)]
+struct ANFSaveData { tags: HashMap<String, NID>, nodes: Vec<Vhl> }
+
 /// (v AND hi) XOR lo
 // TODO /// (ALL(v0..v1) AND hi) XOR lo
 // TODO: /// The v0..v1 thing is used to collapse long chains of nodes where lo=O.
@@ -65,7 +75,9 @@ impl Base for ANFBase {
     self.walk(n, &mut |n,_,__,lo| w!("  \"{:?}\"->\"{:?}\";", n, lo));
     w!("}}"); }
 
-  fn def(&mut self, _s:String, _v:VID)->NID { todo!("anf::def"); }
+  fn def(&mut self, s:String, v:VID)->NID {
+    let nid = NID::from_vid(v);
+    self.tag(nid, format!("{}{:?}", s, v)) }
   // TODO: tag and get are copied verbatim from bdd
   fn tag(&mut self, n:NID, s:String)->NID { self.tags.insert(s, n); n }
   fn get(&self, s:&str)->Option<NID> { Some(*self.tags.get(s)?) }
@@ -139,6 +151,17 @@ impl Base for ANFBase {
   fn solution_set(&self, n: NID, nvars: usize)->HashSet<Reg> {
     self.solutions_pad(n, nvars).collect() }
 
+  fn node_count(&self, n:NID)->usize {
+    let mut c = 0;
+    if !n.is_const() { self.walk(n, &mut |_,_,_,_| c+=1) }
+    c }
+
+  fn support(&self, n:NID)->HashSet<VID> {
+    let mut res = HashSet::new();
+    if !n.is_const() { self.walk(n, &mut |_,v,_,_| { res.insert(v); }) }
+    res.remove(&VID::top()); res.remove(&VID::nov());
+    res }
+
 } // impl Base for ANFBase
 
 // internal ANFBase implementation
@@ -224,7 +247,7 @@ impl ANFBase {
         let lo = self.xor(c, r);
         self.vhl(a, hi, lo)}}}
 
-  pub fn solutions_pad(&self, n:NID, nvars:usize)->ANFSolIterator {
+  pub fn solutions_pad(&self, n:NID, nvars:usize)->ANFSolIterator<'_> {
     ANFSolIterator::from_anf_base(self, n, nvars)}
 } // impl ANFBase
 
@@ -247,8 +270,8 @@ impl ANFBase {
 
   pub fn first_term(&self, n:NID)->Option<Cursor> {
     if n == O { return None } // O has no other terms, and we can't represent O with a cursor
-    let nvars = n.vid().var_ix();
-    let mut cur = Cursor::new(nvars, n); // vid().var_ix()+1
+    let nvars = n.vid().var_ix()+1;
+    let mut cur = Cursor::new(nvars, n);
     cur.descend(self); // walk down the lo branches to lowest term (O or I)
     Some(cur) }
 
@@ -267,8 +290,62 @@ impl ANFBase {
       cur.descend(self);                         self.log(&cur, "descend");
       if cur.node == I { self.log(&cur, "<-- answer (lo)"); return Some(cur) }}}
 
-  pub fn terms(&self, n:NID)->ANFTermIterator {
-    ANFTermIterator::from_anf_base(self, n) }}
+  /// write the nodes and tags to a file.
+  pub fn save(&self, path:&str)->io::Result<()> {
+    let data = ANFSaveData{ tags: self.tags.clone(), nodes: self.nodes.clone() };
+    write_versioned(path, ANF_FORMAT_VERSION, &data) }
+
+  /// load a base previously written by `save`.
+  pub fn load(path:&str)->io::Result<ANFBase> {
+    let ANFSaveData{ tags, nodes } = read_versioned(path, ANF_FORMAT_VERSION, "anf")?;
+    let cache = nodes.iter().enumerate().map(|(i, &vhl)| (vhl, NID::from_vid_idx(vhl.v, i))).collect();
+    Ok(ANFBase{ nodes, cache, tags }) }
+
+  pub fn terms(&self, n:NID)->ANFTermIterator<'_> {
+    ANFTermIterator::from_anf_base(self, n) }
+
+  /// canonical list of terms: each term is a sorted list of its variables,
+  /// and the terms themselves are sorted by degree, then lexicographically.
+  /// (the constant term 1, if present, shows up as an empty vec.)
+  pub fn monomials(&self, n:NID)->Vec<Vec<VID>> {
+    let mut res:Vec<Vec<VID>> = self.terms(n.raw()).map(|t| {
+      let mut vs:Vec<VID> = t.hi_bits().iter().map(|&i| VID::var(i as u32)).collect();
+      vs.sort(); vs }).collect();
+    if n.is_inv() { res.push(vec![]) }
+    res.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    res }
+
+  /// build a polynomial from a truth table, using the (fast) Möbius transform.
+  /// bits[i] is the output when each vars[j] is set to bit j of i, so
+  /// bits.len() must be 2^vars.len().
+  pub fn from_tt(&mut self, bits:&[bool], vars:&[VID])->NID {
+    assert_eq!(bits.len(), 1 << vars.len(), "from_tt: need 2^{} bits", vars.len());
+    let mut coeffs = bits.to_vec();
+    for j in 0..vars.len() {
+      for i in 0..coeffs.len() {
+        if i & (1 << j) != 0 { coeffs[i] ^= coeffs[i ^ (1 << j)] }}}
+    let mut res = O;
+    for (i, &c) in coeffs.iter().enumerate() {
+      if !c { continue }
+      let mut term = I;
+      for (j, &v) in vars.iter().enumerate() {
+        if i & (1 << j) != 0 { term = self.and(term, NID::from_vid(v)) }}
+      res = self.xor(res, term) }
+    res }
+
+  /// algebraic degree: the number of variables in the largest monomial.
+  /// (computed directly from the graph, without enumerating the terms.)
+  pub fn degree(&self, n:NID)->usize {
+    let mut memo = HashMap::new();
+    self.degree_aux(n.raw(), &mut memo) }
+
+  fn degree_aux(&self, n:NID, memo:&mut HashMap<NID,usize>)->usize {
+    if n.is_const() { return 0 }
+    if let Some(&d) = memo.get(&n) { return d }
+    let Vhl{ v:_, hi, lo } = self.fetch(n);
+    let d = std::cmp::max(1 + self.degree_aux(hi.raw(), memo), self.degree_aux(lo.raw(), memo));
+    memo.insert(n, d);
+    d }}
 
 pub struct ANFTermIterator<'a> {
   base: &'a ANFBase,
@@ -291,36 +368,88 @@ impl Iterator for ANFTermIterator<'_> {
     else { None }}}
 
 
-/// iterator for actual solutions.
-/// this works by converting to a bdd.
+/// iterator for actual solutions, in the same order as the solutions of the
+/// equivalent bdd (increasing `Reg::as_usize`).
+///
+/// Rather than converting the whole polynomial to a bdd up front, this walks
+/// down one path of that bdd at a time, taking the cofactors as it goes:
+/// for f = (v AND hi) XOR lo, we have f|v=0 = lo and f|v=1 = hi XOR lo. Since
+/// anf is canonical, any cofactor other than O has a solution, so the walk
+/// never has to backtrack out of a dead branch, and the first solution only
+/// costs one xor per variable. The cofactors are calculated in a private copy
+/// of the polynomial, so the source base isn't modified.
 pub struct ANFSolIterator<'a> {
   _anf: &'a ANFBase,
-  bdd: BddBase,
-  //acur: Option<Cursor>,
-  bcur: Option<Cursor>}
+  /// private copy of the polynomial, where the cofactors get calculated
+  scratch: ANFBase,
+  nvars: usize,
+  /// fs[k] is the cofactor after assigning the top k variables (so fs[0] is the root)
+  fs: Vec<NID>,
+  /// alt[k] is the cofactor for setting the (k+1)th var from the top to 1 instead (or O)
+  alt: Vec<NID>,
+  reg: Reg,
+  done: bool }
 
 impl<'a>  ANFSolIterator<'a> {
   pub fn from_anf_base(anf: &'a ANFBase, nid:NID, nvars:usize)->Self {
-    let mut bdd = BddBase::new();
-    // TODO: convert ANF->BDD incrementally, to speed up time to first solution.
-    // This will involve copying bcur.scope but changing the actual nids on the stack.
-    //let acur = anf.first_term(nvars, nid);
-    let bnid = anf.to_base(nid, &mut bdd);
-    let bcur = bdd.first_solution(bnid, nvars);
-    ANFSolIterator{ _anf:anf, bdd, bcur } }}
+    if !nid.is_const() {
+      assert!(nid.vid().var_ix() < nvars, "{} depends on more than {} variables", nid, nvars) }
+    let mut scratch = ANFBase::new();
+    let root = scratch.import(anf, nid, &mut HashMap::new());
+    let mut fs = vec![O; nvars+1]; fs[0] = root;
+    let mut res = ANFSolIterator{ _anf:anf, scratch, nvars, fs, alt: vec![O; nvars], reg: Reg::new(nvars), done: root == O };
+    if !res.done { res.descend(0) }
+    res }
+
+  /// split f into its (lo, hi) cofactors with respect to input var i.
+  fn cofactors(&mut self, f:NID, i:usize)->(NID, NID) {
+    if f.is_const() || f.vid() != VID::var(i as u32) { return (f, f) }
+    let Vhl{ v:_, hi, lo } = self.scratch.fetch(f);
+    let hi = self.scratch.xor(hi, lo);
+    (lo, hi) }
+
+  /// assign the vars from level k down, always taking the lo branch when it has a solution.
+  fn descend(&mut self, k:usize) {
+    for k in k..self.nvars {
+      let i = self.nvars - 1 - k;
+      let (lo, hi) = self.cofactors(self.fs[k], i);
+      if lo == O { self.reg.put(i, true); self.fs[k+1] = hi; self.alt[k] = O }
+      else { self.reg.put(i, false); self.fs[k+1] = lo; self.alt[k] = hi }}
+    debug_assert_eq!(self.fs[self.nvars], I) }
+
+  /// move to the next solution: flip the lowest var that can go from 0 to 1, then descend.
+  fn advance(&mut self) {
+    for k in (0..self.nvars).rev() {
+      if self.alt[k] != O {
+        let i = self.nvars - 1 - k;
+        self.reg.put(i, true); self.fs[k+1] = self.alt[k]; self.alt[k] = O;
+        self.descend(k+1);
+        return }}
+    self.done = true }}
 
 impl Iterator for ANFSolIterator<'_> {
   type Item = Reg;
   fn next(&mut self)->Option<Self::Item> {
-    if let Some(cur) = self.bcur.take() {
-      let res = Some(cur.scope.clone());
-      self.bcur = self.bdd.next_solution(cur);
-      res }
-    else { None } }}
+    if self.done { return None }
+    let res = self.reg.clone();
+    self.advance();
+    Some(res) }}
+
 
-
 impl ANFBase {
 
+  /// copy node n from another ANFBase into this one.
+  fn import(&mut self, src:&ANFBase, n:NID, memo:&mut HashMap<NID,NID>)->NID {
+    if n.is_const() || n.is_vid() { return n }
+    let r = n.raw();
+    let res = if let Some(&res) = memo.get(&r) { res } else {
+      let Vhl{ v, hi, lo } = src.fetch(r);
+      let (hi, lo) = (self.import(src, hi, memo), self.import(src, lo, memo));
+      let res = self.vhl(v, hi, lo);
+      memo.insert(r, res);
+      res };
+    if n.is_inv() { !res } else { res }}
+
   /// transfer node to another base (e.g. bdd), and return the NID from that base.
   pub fn to_base(&self, n:NID, dest: &mut dyn Base)->NID {
     let mut sum = nid::O;
@@ -332,12 +461,90 @@ impl ANFBase {
         println!("term: {}", term) }
       sum = dest.xor(sum, term);
       println!("sum: {}", sum) }
-    sum }}
+    sum }
+
+  /// evaluate the polynomial for the given assignment of input variables.
+  pub fn eval(&self, n:NID, reg:&Reg)->bool {
+    if n.is_const() { return n == I }
+    let Vhl{ v, hi, lo } = self.fetch(n);
+    (reg.var_get(v) && self.eval(hi, reg)) ^ self.eval(lo, reg) }
+
+  /// k-th elementary symmetric polynomial: the xor of all degree-k monomials over vars.
+  pub fn elementary_symmetric(&mut self, vars:&[VID], k:usize)->NID {
+    if k > vars.len() { return O }
+    // row[j] = e_j over the variables seen so far. e_j(xs+[v]) = e_j(xs) ^ v*e_(j-1)(xs)
+    let mut row = vec![O; k+1]; row[0] = I;
+    for &v in vars {
+      let x = NID::from_vid(v);
+      for j in (1..=k).rev() {
+        let t = self.and(x, row[j-1]);
+        row[j] = self.xor(row[j], t) }}
+    row[k] }
+
+  /// does the vector of output polynomials define a bijection on `nvars` input bits?
+  /// (that is: does each output pattern occur for exactly one input?)
+  pub fn is_permutation(&mut self, outputs:&[NID], nvars:usize)->bool {
+    if outputs.len() != nvars { return false }
+    let mut seen = vec![false; 1 << nvars];
+    let mut reg = Reg::new(nvars);
+    for _ in 0..seen.len() {
+      let mut out = 0;
+      for (i, &o) in outputs.iter().enumerate() { if self.eval(o, &reg) { out |= 1 << i }}
+      if seen[out] { return false }
+      seen[out] = true;
+      if nvars > 0 { reg.increment(); }}
+    true }
+
+  /// Tseitin encoding of n as a list of DIMACS-style clauses. Input `VID::var(k)` is
+  /// numbered `k+1`, and each internal node gets a fresh number above all the inputs.
+  /// Each node (v ∧ hi) ⊕ lo becomes product clauses for the ∧ and parity clauses for the ⊕.
+  /// The clauses are satisfiable exactly when n is, and each solution of n extends
+  /// to exactly one solution of the clauses.
+  pub fn to_cnf(&self, n:NID)->Vec<Vec<i64>> {
+    if n == I { return vec![] }
+    if n == O { return vec![vec![]] }
+    let next = self.support(n).iter().map(|v| v.var_ix() as i64 + 1).max().unwrap_or(0);
+    let mut cnf = CnfBuilder { next, memo: HashMap::new(), clauses: vec![] };
+    let top = cnf.lit(self, n);
+    cnf.clauses.push(vec![top]);
+    cnf.clauses }}
+
+/// helper for `ANFBase::to_cnf`: tracks the fresh variable numbers and the clauses so far.
+struct CnfBuilder { next:i64, memo:HashMap<NID,i64>, clauses:Vec<Vec<i64>> }
+
+impl CnfBuilder {
+  fn fresh(&mut self)->i64 { self.next += 1; self.next }
+
+  /// dimacs literal for nid n, adding clauses to define it if necessary.
+  fn lit(&mut self, anf:&ANFBase, n:NID)->i64 {
+    let sign = if n.is_inv() { -1 } else { 1 };
+    let raw = n.raw();
+    if let Some(&x) = self.memo.get(&raw) { return sign * x }
+    let x =
+      if raw.is_const() { // only needed when a node has a constant branch
+        let t = self.fresh(); self.clauses.push(vec![if raw == I { t } else { -t }]); t }
+      else if raw.is_vid() { raw.vid().var_ix() as i64 + 1 }
+      else {
+        let Vhl{ v, hi, lo } = anf.fetch(raw);
+        let v = v.var_ix() as i64 + 1;
+        // a = v ∧ hi
+        let a = if hi == I { v } else {
+          let h = self.lit(anf, hi); let a = self.fresh();
+          self.clauses.extend([vec![-a, v], vec![-a, h], vec![a, -v, -h]]);
+          a };
+        // t = a ⊕ lo
+        if lo == O { a } else {
+          let l = self.lit(anf, lo); let t = self.fresh();
+          self.clauses.extend([vec![-t, a, l], vec![-t, -a, -l], vec![t, -a, l], vec![t, a, -l]]);
+          t }};
+    self.memo.insert(raw, x);
+    sign * x }}
 
 
 // test suite
 test_base_consts!(ANFBase);
 test_base_when!(ANFBase);
+test_base_imp_iff!(ANFBase);
 
 #[test] fn test_anf_hilo() {
   let base = ANFBase::new();
@@ -499,6 +706,16 @@ test_base_when!(ANFBase);
   let x = expr![anf, ((a & (b^c)) ^ (b & (c^I)))]; // b^ba^ca^cb
   let t:Vec<_> = anf.terms(x).map(|r|r.as_usize()).collect();
   assert_eq!(t, vec![0b010,0b011,0b101,0b110]); }
+
+#[test] fn test_anf_terms_len() {
+  // each term should have room for every variable up to and including the top one.
+  // (the cursor used to be one bit short, so hi_bits() dropped the top variable.)
+  let mut anf = ANFBase::new();
+  let (a,c) = (NID::var(0), NID::var(2));
+  let x = expr![anf, ((a & c) ^ c)];
+  for t in anf.terms(x) { assert_eq!(t.len(), 3) }
+  let t:Vec<_> = anf.terms(x).map(|r| r.hi_bits()).collect();
+  assert_eq!(t, vec![vec![2], vec![0, 2]]); }
 
 #[test] fn test_anf_to_base() {
   use crate::bdd::BddBase;
@@ -518,3 +735,126 @@ test_base_when!(ANFBase);
   let expect  = expr![bdd, ((a & (b^c)) ^ (b & (c^I)))];
   let actual  = anf.to_base(initial, &mut bdd);
   assert_eq!(expect, actual, "anf-> bdd should get same answer as pure bdd (3).");}
+
+#[test] fn test_anf_is_permutation() {
+  let mut anf = ANFBase::new();
+  let (a,b,c) = (NID::var(0), NID::var(1), NID::var(2));
+  // each output only mixes in bits "below" it, so this is invertible:
+  let sbox = [expr![anf, (a ^ (b & c))], b, expr![anf, (c ^ b)]];
+  assert!(anf.is_permutation(&sbox, 3));
+  let bad = [expr![anf, (a & b)], b, c];
+  assert!(!anf.is_permutation(&bad, 3)); }
+
+#[test] fn test_anf_monomials() {
+  let mut anf = ANFBase::new();
+  let (a,b,c) = (NID::var(0), NID::var(1), NID::var(2));
+  let x = expr![anf, ((a & (b^c)) ^ (b & (c^I)))];
+  let y = expr![anf, (((c & b) ^ b) ^ ((c & a) ^ (b & a)))];
+  let (va, vb, vc) = (a.vid(), b.vid(), c.vid());
+  let mut expect = vec![vec![vb], vec![va,vb], vec![va,vc], vec![vb,vc]];
+  for t in expect.iter_mut() { t.sort() }
+  expect.sort_by(|p, q| p.len().cmp(&q.len()).then_with(|| p.cmp(q)));
+  assert_eq!(anf.monomials(x), expect);
+  assert_eq!(anf.monomials(x), anf.monomials(y));
+  assert_eq!(anf.monomials(x), anf.monomials(x), "monomials should be stable");
+  assert_eq!(anf.monomials(!a), vec![vec![], vec![va]]);
+  assert_eq!(anf.monomials(O), Vec::<Vec<VID>>::new()); }
+
+#[test] fn test_anf_elementary_symmetric() {
+  let mut anf = ANFBase::new();
+  let (a,b,c) = (NID::var(0), NID::var(1), NID::var(2));
+  let vs = [a.vid(), b.vid(), c.vid()];
+  assert_eq!(anf.elementary_symmetric(&vs, 0), I);
+  assert_eq!(anf.elementary_symmetric(&vs, 1), expr![anf, ((a ^ b) ^ c)]);
+  assert_eq!(anf.elementary_symmetric(&vs, 2), expr![anf, (((a & b) ^ (a & c)) ^ (b & c))]);
+  assert_eq!(anf.elementary_symmetric(&vs, 3), expr![anf, ((a & b) & c)]);
+  assert_eq!(anf.elementary_symmetric(&vs, 4), O); }
+
+#[test] fn test_anf_save_load() {
+  let mut base = ANFBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let n = expr![base, ((x0 & x1) ^ ((x1 & (x2 & x3)) ^ (x0 ^ x3)))];
+  base.tag(n, "n".to_string());
+  let path = std::env::temp_dir().join("bex-test-anf.anf");
+  let path = path.to_str().unwrap();
+  base.save(path).expect("failed to save");
+  let mut loaded = ANFBase::load(path).expect("failed to load");
+  let m = loaded.get("n").expect("tag should survive the round trip");
+  assert_eq!(m, n);
+  let terms:HashSet<Reg> = base.terms(n).collect();
+  assert_eq!(loaded.terms(m).collect::<HashSet<Reg>>(), terms);
+  // the rebuilt cache should find existing nodes instead of adding new ones:
+  let len = loaded.nodes.len();
+  assert_eq!(expr![loaded, ((x0 & x1) ^ ((x1 & (x2 & x3)) ^ (x0 ^ x3)))], n);
+  assert_eq!(loaded.nodes.len(), len); }
+
+#[test] fn test_anf_def() {
+  let mut base = ANFBase::new();
+  let v = VID::var(3);
+  let n = base.def("x".to_string(), v);
+  assert_eq!(n, NID::from_vid(v));
+  assert_eq!(base.get(&format!("x{:?}", v)), Some(n)); }
+
+#[test] fn test_anf_degree() {
+  let mut base = ANFBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let a = expr![base, (x0 & (x1 & x2))];
+  assert_eq!(base.degree(a), 3);
+  let b = expr![base, (x0 ^ x1)];
+  assert_eq!(base.degree(b), 1);
+  assert_eq!(base.degree(!x0), 1);
+  assert_eq!(base.degree(I), 0);
+  assert_eq!(base.degree(O), 0);
+  let n = expr![base, ((x0 & x3) ^ ((x1 & (x2 & x3)) ^ x2))];
+  let expect = base.terms(n).map(|t| t.hi_bits().len()).max().unwrap();
+  assert_eq!(base.degree(n), expect);
+  assert_eq!(base.degree(!n), 3); }
+
+#[test] fn test_anf_from_tt() {
+  let mut base = ANFBase::new();
+  let vars:Vec<VID> = (0..3).map(VID::var).collect();
+  let maj:Vec<bool> = (0..8).map(|i:u32| i.count_ones() >= 2).collect();
+  let n = base.from_tt(&maj, &vars);
+  let [x0, x1, x2] = [vars[0], vars[1], vars[2]];
+  // x0x1 ^ x0x2 ^ x1x2 (monomials() lists the topmost variables first)
+  assert_eq!(base.monomials(n), vec![vec![x2, x1], vec![x2, x0], vec![x1, x0]]);
+  // check the truth table round trip:
+  for (i, &b) in maj.iter().enumerate() {
+    assert_eq!(base.eval(n, &Reg::from_bits(3, &(0..3).filter(|j| i & (1<<j) != 0).collect::<Vec<_>>())), b) }
+  assert_eq!(base.from_tt(&[false; 8], &vars), O);
+  assert_eq!(base.from_tt(&[true; 8], &vars), I);
+  assert_eq!(base.from_tt(&[true, false], &vars[..1]), !NID::from_vid(x0)); }
+
+#[test] fn test_anf_to_cnf() {
+  let mut anf = ANFBase::new();
+  let (a,b,c,d) = (NID::var(0), NID::var(1), NID::var(2), NID::var(3));
+  let x = expr![anf, (((a & (b^c)) ^ (b & (c^I))) ^ (d & a))];
+  for n in [x, !x, a, !b, expr![anf, (a & b)], O, I] {
+    let cnf = anf.to_cnf(n);
+    let mut bdd = BddBase::new();
+    // the fresh variables start right after the inputs in the support:
+    let ninputs = anf.support(n).iter().map(|v| v.var_ix() + 1).max().unwrap_or(1);
+    let nvars = cnf.iter().flatten().map(|x| x.unsigned_abs() as usize).max().unwrap_or(0).max(ninputs);
+    let mut top = I;
+    for clause in &cnf {
+      let lits:Vec<NID> = clause.iter().map(|&x| {
+        let v = NID::var(x.unsigned_abs() as u32 - 1);
+        if x < 0 { !v } else { v }}).collect();
+      let c = bdd.clause(&lits); top = bdd.and(top, c) }
+    assert_eq!(bdd.solution_count(top, nvars) as usize, anf.solution_set(n, ninputs).len(),
+      "cnf for {} should have one solution per solution of the anf", n); }}
+
+/// the lazy solution iterator should give the same solutions, in the same order,
+/// as converting the whole polynomial to a bdd first.
+#[test] fn test_anf_solutions_lazy() {
+  let mut anf = ANFBase::new();
+  let (a,b,c,d) = (NID::var(0), NID::var(1), NID::var(2), NID::var(3));
+  let x = expr![anf, (((a & (b^c)) ^ (b & (c^I))) ^ (d & a))];
+  let y = expr![anf, ((a & c) ^ d)]; // (b is free)
+  for n in [x, !x, y, !y, a, !d, expr![anf, (a & (b & (c & d)))], O, I] {
+    let mut bdd = BddBase::new();
+    let bn = anf.to_base(n, &mut bdd);
+    let eager:Vec<Reg> = bdd.solutions_pad(bn, 4).collect();
+    let lazy:Vec<Reg> = anf.solutions_pad(n, 4).collect();
+    assert_eq!(lazy.first(), eager.first(), "first solution for {}", n);
+    assert_eq!(lazy, eager, "all solutions for {}", n); }}
@@ -3,7 +3,7 @@
 use std::collections::{HashMap,HashSet};
 
 use crate::base::*;
-use crate::{nid, NID, Fun};
+use crate::{nid, nid::NidFun, NID, Fun};
 use crate::{vid, vid::VID};
 use crate::{ops, ops::Ops};
 use crate::simp;
@@ -35,6 +35,9 @@ impl RawASTBase {
         self.hash.insert(ops, nid);
         nid }}}
 
+  /// add an Ops to the base as-is, bypassing the simplification rules.
+  /// (identical ops are still shared.)
+  pub fn push_raw_ops(&mut self, ops:Ops)->NID { self.nid(ops) }
 
   fn when(&mut self, v:vid::VID, val:NID, nid:NID)->NID {
     if nid.is_vid() && nid.vid() == v { val }
@@ -48,6 +51,22 @@ impl RawASTBase {
 
 
 
+  /// replace variable v with n everywhere in ctx, caching rebuilt (raw) sub-expressions.
+  fn sub_aux(&mut self, v:vid::VID, n:NID, ctx:NID, cache:&mut HashMap<NID,NID>)->NID {
+    let res =
+      if ctx.is_vid() { if ctx.vid() == v { n } else { ctx.raw() }}
+      else if ctx.is_lit() { ctx.raw() }
+      else if let Some(&res) = cache.get(&ctx.raw()) { res }
+      else {
+        let ops = self.get_ops(ctx.raw()).clone();
+        let rpn:Vec<NID> = ops.to_rpn().map(|&x|{
+          if x.is_fun() { x }
+          else { self.sub_aux(v, n, x, cache) }}).collect();
+        let res = self.nid(ops::rpn(&rpn));
+        cache.insert(ctx.raw(), res);
+        res };
+    if ctx.is_inv() { !res } else { res }}
+
   fn walk<F>(&self, n:NID, f:&mut F) where F: FnMut(NID) {
     let mut seen = HashSet::new();
     self.step(n,f,&mut seen)}
@@ -108,7 +127,30 @@ impl RawASTBase {
       let f = |x:&NID| res[x.idx()].push(n);
       bit.to_rpn().rev().skip(1).for_each(f); });
     res }
+
+  /// fan-out of each internal node reachable from top: that is, how many distinct
+  /// (reachable) parents refer to it. Nodes with high fan-out are the ones worth
+  /// caching when converting the AST to some other form.
+  pub fn common_subexpr_stats(&self, top:NID)->HashMap<NID, usize> {
+    let mut reachable = HashSet::new();
+    self.walk(top, &mut |n| if n.is_ixn() { reachable.insert(n.raw()); });
+    // count each (reachable) parent once per distinct internal child:
+    let mut res:HashMap<NID, usize> = reachable.iter().map(|&n| (n, 0)).collect();
+    for &p in reachable.iter() {
+      let kids:HashSet<NID> = self.bits[p.idx()].to_rpn().rev().skip(1)
+        .filter(|x| x.is_ixn()).map(|x| x.raw()).collect();
+      for k in kids { *res.get_mut(&k).unwrap() += 1 }}
+    res }
 
+  /// how many of the internal nodes reachable from n use each operator
+  /// (the function nid at the end of each node's rpn, like `ops::AND`).
+  pub fn op_histogram(&self, n:NID)->HashMap<NidFun, usize> {
+    let mut res:HashMap<NidFun, usize> = HashMap::new();
+    self.walk(n, &mut |x| if x.is_ixn() {
+      let (f, _) = self.get_ops(x).to_app();
+      *res.entry(f.to_fun().unwrap()).or_default() += 1; });
+    res }
+
   /// this is part of the garbage collection system. keep is the top level nid to keep.
   /// seen gets marked true for every nid that is a dependency of keep.
   /// TODO:: use a HashSet for 'seen' in markdeps()
@@ -163,6 +205,32 @@ impl RawASTBase {
     (self.permute(&kept), keep.iter().map(|&i|
       NID::ixn(new[i.idx()].expect("?!"))).collect()) }
 
+  /// Construct a copy of the base with the simplification rules re-applied to
+  /// every node (e.g. to clean up after `push_raw_ops` or substitutions).
+  /// Tags are remapped to the corresponding nodes in the new base.
+  pub fn cleanup(&mut self)->RawASTBase {
+    let mut res = RawASTBase::empty();
+    let mut new:Vec<NID> = Vec::with_capacity(self.bits.len());
+    let nn = |x:NID, new:&Vec<NID>| {
+      if x.is_ixn() { let r = new[x.idx()]; if x.is_inv() { !r } else { r }} else { x }};
+    for bit in self.bits.iter() {
+      let (f, args0) = bit.to_app();
+      let args:Vec<NID> = args0.iter().map(|&x| nn(x, &new)).collect();
+      let n = match (f.to_fun(), args.as_slice()) {
+        (Some(ops::AND), &[x, y]) => res.and(x, y),
+        (Some(ops::XOR), &[x, y]) => res.xor(x, y),
+        (Some(ops::VEL), &[x, y]) => res.or(x, y),
+        _ => { let mut rpn = args; rpn.push(f); res.nid(ops::rpn(&rpn)) }};
+      new.push(n) }
+    for (key, &n) in &self.tags { res.tags.insert(key.clone(), nn(n, &new)); }
+    res }
+
+  /// return the node tagged with the given name, or else call build()
+  /// to construct it, and tag the result for next time.
+  pub fn get_or_build(&mut self, tag:&str, build:impl FnOnce(&mut Self)->NID)->NID {
+    if let Some(&n) = self.tags.get(tag) { n }
+    else { let n = build(self); self.tag(n, tag.to_string()) }}
+
   pub fn get_ops(&self, n:NID)->&Ops {
     if n.is_ixn() { &self.bits[n.idx()] }
     else { panic!("nid {n} is not an ixn...") }}
@@ -196,31 +264,99 @@ impl RawASTBase {
       .map(|(i,&x)|(NID::var(i as u32), x)).collect();
     self.eval(f, &env) }
 
-  /// recursively evaluate an AST, caching shared sub-expressions
+  /// render an AST node in bex operator notation:
+  /// `*` for and, `+` for or, `%` for xor, `~` for not.
+  /// Any other function is written in call form, e.g. `t1001(x0, x1)`.
+  pub fn to_sexpr(&self, n:NID)->String { self.sexpr_aux(n, true) }
+
+  fn sexpr_aux(&self, n:NID, top:bool)->String {
+    if n.is_inv() { return format!("~{}", self.sexpr_aux(n.raw(), false)) }
+    if !n.is_ixn() { return n.to_string() }
+    let (f, args) = self.get_ops(n).to_app();
+    let op = match f.to_fun() {
+      Some(ops::AND) => "*",
+      Some(ops::VEL) => "+",
+      Some(ops::XOR) => "%",
+      _ => {
+        let args:Vec<String> = args.iter().map(|&x| self.sexpr_aux(x, true)).collect();
+        return format!("{}({})", f, args.join(", ")) }};
+    let s = format!("{} {} {}", self.sexpr_aux(args[0], false), op, self.sexpr_aux(args[1], false));
+    if top { s } else { format!("({})", s) }}
+
+  /// if-then-else, built from the simplifying and/or constructors.
+  pub fn ite(&mut self, i:NID, t:NID, e:NID)->NID {
+    if i == nid::I || t == e { t }
+    else if i == nid::O { e }
+    else {
+      let it = self.and(i, t);
+      let ie = self.and(!i, e);
+      self.or(it, ie) }}
+
+  /// parse an expression in bex operator notation and build it in this base.
+  /// From loosest to tightest binding:
+  ///
+  /// - `c ? t : e` if-then-else
+  /// - `x = y` equivalence
+  /// - `x / y` implication (`~x + y`, as in `simp::imp`). This one groups to the right.
+  /// - `x + y` or
+  /// - `x % y` xor
+  /// - `x * y` and, `x < y` less than (`~x * y`)
+  /// - `~x` not
+  ///
+  /// Leaves are input variables (`x0`, `v1`... with hex indices) and the constants `O` and `I`.
+  /// Parentheses (and `?:`) can nest up to `MAX_PARSE_DEPTH` levels deep.
+  pub fn parse(&mut self, s:&str)->Result<NID, String> {
+    let mut p = Parser{ toks:tokenize(s)?, pos:0, depth:0 };
+    let res = p.expr(self)?;
+    if let Some(t) = p.peek() { Err(format!("unexpected '{}' at token {}", t, p.pos)) }
+    else { Ok(res) }}
+
+  /// value of the (raw) nid n, if we can find it without evaluating any sub-expressions.
+  fn eval_known(n:NID, kvs:&HashMap<NID, NID>, cache:&HashMap<NID,NID>)->Option<NID> {
+    if let Some(&vn) = kvs.get(&n) { Some(vn) }
+    else if n.is_lit() { Some(n) }
+    else if n.is_fun() {
+      let mut f = n.to_fun().unwrap();
+      loop {
+        let i = f.arity();
+        if i == 0 { return Some(if f.tbl()==0 { nid::O } else { nid::I }) }
+        else {
+          let &arg = kvs.get(&NID::var((i as u32)-1))
+            .expect("don't have enough args to fully evaluate!");
+          f = f.when(i-1, arg==nid::I); }}}
+    else { cache.get(&n).cloned() }}
+
+  /// evaluate an AST, caching shared sub-expressions. This uses an explicit stack
+  /// rather than recursion, because the ASTs for large multipliers can get very deep.
   fn eval_aux(&mut self, n:NID, kvs:&HashMap<NID, NID>, cache:&mut HashMap<NID,NID>)->NID {
-    let raw = n.raw();
-    let res =
-      if let Some(&vn) = kvs.get(&raw) { vn }
-      else if n.is_lit() { raw }
-      else if n.is_fun() {
-        let mut f = n.to_fun().unwrap();
-        let res:NID;
-        loop {
-          let i = f.arity();
-          if i == 0 { res = if f.tbl()==0 { nid::O } else { nid::I}; break; }
-          else {
-            let &arg = kvs.get(&NID::var((i as u32)-1))
-              .expect("don't have enough args to fully evaluate!");
-            f = f.when(i-1, arg==nid::I); }};
-        res }
-      else if let Some(&vn) = cache.get(&raw) { vn }
+    // each entry is (raw nid, whether its args have already been pushed)
+    let mut stack = vec![(n.raw(), false)];
+    while let Some((raw, expanded)) = stack.pop() {
+      if Self::eval_known(raw, kvs, cache).is_some() { continue }
+      let (f, args0) = self.get_ops(raw).to_app();
+      if !expanded {
+        stack.push((raw, true));
+        // (pushed in reverse, so the args are evaluated from left to right)
+        for &x in args0.iter().rev() {
+          if Self::eval_known(x.raw(), kvs, cache).is_none() { stack.push((x.raw(), false)) }}}
       else {
-        let (f, args0) = self.get_ops(raw).to_app();
-        let args:Vec<NID> = args0.iter().map(|&x| self.eval_aux(x, kvs, cache)).collect();
-        let t = self.apply(f, args); cache.insert(n, t); t };
+        let args:Vec<NID> = args0.iter().map(|&x| {
+          let v = Self::eval_known(x.raw(), kvs, cache).expect("arg should have been evaluated");
+          if x.is_inv() { !v } else { v }}).collect();
+        let t =
+          if args.iter().all(|x| x.is_const()) { self.apply(f, args) }
+          else { // partial evaluation: rebuild through the simplifying constructors
+            match (f.to_fun(), args.as_slice()) {
+              (Some(ops::AND), &[x, y]) => self.and(x, y),
+              (Some(ops::XOR), &[x, y]) => self.xor(x, y),
+              (Some(ops::VEL), &[x, y]) => self.or(x, y),
+              _ => { let mut rpn = args; rpn.push(f); self.nid(ops::rpn(&rpn)) }}};
+        cache.insert(raw, t); }}
+    let res = Self::eval_known(n.raw(), kvs, cache).unwrap();
     if n.is_inv() { !res } else { res }}
 
-  /// evaluate a list of nids (substituting in the given values)
+  /// evaluate a list of nids (substituting in the given values).
+  /// variables missing from `kvs` are left alone, so the result may be a new AST node.
   pub fn eval_all(&mut self, nids:&[NID], kvs:&HashMap<NID, NID>)->Vec<NID> {
     let mut cache = HashMap::new();
     nids.iter().map(|&n| self.eval_aux(n, kvs, &mut cache)).collect() }
@@ -229,8 +365,171 @@ impl RawASTBase {
   pub fn eval(&mut self, nid:NID, kvs:&HashMap<NID, NID>)->NID {
     self.eval_all(&[nid], kvs)[0] }
 
+
+  /// like dot(), but shade each node by its cost (as computed by masks_and_costs),
+  /// from light gray for the cheapest nodes to red for the most expensive ones.
+  pub fn dot_by_cost(&self, n:NID, wr: &mut dyn std::fmt::Write) {
+    let (_, costs) = self.masks_and_costs(|_, _| 0);
+    self.dot_aux(n, Some(&costs), wr) }
+
+  /// shared implementation of dot() and dot_by_cost(). If costs are given, each node
+  /// is filled with a color from light gray (cheapest) to red (most expensive).
+  fn dot_aux(&self, n:NID, costs:Option<&[u32]>, wr: &mut dyn std::fmt::Write) {
+    let max = costs.and_then(|cs| cs.iter().max().copied()).unwrap_or(1).max(1);
+    let fill = |x:NID|->String {
+      match costs {
+        None => String::new(),
+        Some(cs) => {
+          let c = if x.is_ixn() { cs[x.idx()] } else { 1 };
+          // inputs cost 1, so they get the lightest color:
+          let t = (c.saturating_sub(1)) as f64 / (max.saturating_sub(1)).max(1) as f64;
+          let mix = |a:u8, b:u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+          format!(", fillcolor=\"#{:02x}{:02x}{:02x}\"", mix(0xdd, 0xcc), mix(0xdd, 0x22), mix(0xdd, 0x22)) }}};
+    macro_rules! w {
+      ($x:expr $(,$xs:expr)*) => { writeln!(wr, $x $(,$xs)*).unwrap() }}
+    macro_rules! dotop {
+      ($s:expr, $n:expr $(,$xs:expr)*) => {{
+        w!("  \"{}\"[label={}{}];", $n.raw(), $s, fill($n.raw())); // draw the node
+        $({ if ($xs).is_inv() { w!("edge[style=dashed];"); }
+            else { w!("edge[style=solid];"); }
+            w!(" \"{}\"->\"{}\";", $xs.raw(), $n.raw()); })* }}}
+
+    w!("digraph bdd {{");
+    w!("  bgcolor=\"#3399cc\"; pad=0.225");
+    w!("  rankdir=BT;"); // put root on top
+    w!("  node[shape=circle, style=filled, fillcolor=\"#dddddd\", fontname=calibri];");
+    w!("  edge[style=solid]");
+    self.walk(n, &mut |n| {
+      match n {
+        nid::O => w!(" \"{}\"[label=⊥];", n),
+        nid::I => w!(" \"{}\"[label=⊤];", n),
+        _ if n.is_vid() => {
+          let color = if costs.is_some() { fill(n.raw()) } else { ", fillcolor=\"#bbbbbb\"".to_string() };
+          w!("\"{}\"[label=\"{}\"{}];", n.raw(), n.vid(), color) }
+        _ => {
+          let rpn: Vec<NID> = self.get_ops(n).to_rpn().cloned().collect();
+          let fun = rpn.last().unwrap().to_fun().unwrap();
+          if 2 == fun.arity() {
+            let (x, y) = (rpn[0], rpn[1]);
+            match fun {
+              ops::AND => dotop!("∧",n,x,y),
+              ops::XOR => dotop!("≠",n,x,y),
+              ops::VEL => dotop!("∨",n,x,y),
+              _ => panic!("unexpected op in dot(): {:?}", n) }}
+          else { panic!("can't dot arbitrary ops yet: {:?}", rpn) }}}});
+    w!("}}"); }
 } // impl RawASTBase
 
+fn tokenize(s:&str)->Result<Vec<String>, String> {
+  let mut res = vec![];
+  let mut chars = s.chars().peekable();
+  while let Some(&c) = chars.peek() {
+    if c.is_whitespace() { chars.next(); }
+    else if "+*%~=</?:()".contains(c) { res.push(c.to_string()); chars.next(); }
+    else if c.is_alphanumeric() || c == '#' {
+      let mut word = String::new();
+      while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || c == '#' || c == '.' { word.push(c); chars.next(); }
+        else { break }}
+      res.push(word) }
+    else { return Err(format!("unexpected character '{}'", c)) }}
+  Ok(res) }
+
+/// how deeply `RawASTBase::parse` lets parentheses and `?:` nest. (The parser is
+/// recursive, so without a limit, deep enough nesting would overflow the stack.)
+pub const MAX_PARSE_DEPTH:usize = 256;
+
+/// recursive descent parser for `RawASTBase::parse`
+struct Parser { toks: Vec<String>, pos: usize, depth: usize }
+
+impl Parser {
+  fn peek(&self)->Option<&str> { self.toks.get(self.pos).map(|t| t.as_str()) }
+
+  fn next(&mut self)->Result<String, String> {
+    let res = self.toks.get(self.pos).cloned().ok_or("unexpected end of input")?;
+    self.pos += 1;
+    Ok(res) }
+
+  fn expect(&mut self, tok:&str)->Result<(), String> {
+    let t = self.next().map_err(|_| format!("expected '{}' at end of input", tok))?;
+    if t == tok { Ok(()) } else { Err(format!("expected '{}' but found '{}' at token {}", tok, t, self.pos-1)) }}
+
+  fn expr(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let c = self.eqv(b)?;
+    if self.peek() == Some("?") {
+      self.pos += 1;
+      self.descend()?;
+      let t = self.expr(b)?;
+      self.expect(":")?;
+      let e = self.expr(b)?;
+      self.depth -= 1;
+      Ok(b.ite(c, t, e)) }
+    else { Ok(c) }}
+
+  /// note that we're one level deeper. (caller decrements `depth` on the way back out)
+  fn descend(&mut self)->Result<(), String> {
+    self.depth += 1;
+    if self.depth > MAX_PARSE_DEPTH { Err("too deeply nested".to_string()) } else { Ok(()) }}
+
+  fn eqv(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let mut x = self.imp(b)?;
+    while self.peek() == Some("=") { self.pos += 1; let y = self.imp(b)?; x = !b.xor(x, y) }
+    Ok(x) }
+
+  fn imp(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    // (collect the operands first, since / groups to the right)
+    let mut xs = vec![self.vel(b)?];
+    while self.peek() == Some("/") { self.pos += 1; xs.push(self.vel(b)?) }
+    let mut y = xs.pop().unwrap();
+    while let Some(x) = xs.pop() { y = b.or(!x, y) }
+    Ok(y) }
+
+  fn vel(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let mut x = self.xor(b)?;
+    while self.peek() == Some("+") { self.pos += 1; let y = self.xor(b)?; x = b.or(x, y) }
+    Ok(x) }
+
+  fn xor(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let mut x = self.and(b)?;
+    while self.peek() == Some("%") { self.pos += 1; let y = self.and(b)?; x = b.xor(x, y) }
+    Ok(x) }
+
+  fn and(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let mut x = self.not(b)?;
+    while let Some(op) = self.peek() {
+      let op = match op { "*" => '*', "<" => '<', _ => break };
+      self.pos += 1;
+      let y = self.not(b)?;
+      x = if op == '*' { b.and(x, y) } else { b.and(!x, y) }}
+    Ok(x) }
+
+  fn not(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let mut inv = false;
+    while self.peek() == Some("~") { self.pos += 1; inv = !inv }
+    let x = self.atom(b)?;
+    Ok(if inv { !x } else { x }) }
+
+  fn atom(&mut self, b:&mut RawASTBase)->Result<NID, String> {
+    let t = self.next()?;
+    if t == "(" {
+      self.descend()?;
+      let x = self.expr(b)?; self.expect(")")?;
+      self.depth -= 1;
+      Ok(x) }
+    else if t.len() == 1 && "+*%=</?:)".contains(t.as_str()) {
+      Err(format!("unexpected '{}' at token {}", t, self.pos-1)) }
+    else { Self::leaf(&t).ok_or_else(|| format!("expected a variable, O, or I but found '{}' at token {}", t, self.pos-1)) }}
+
+  /// input variables and constants are the only leaves. (in particular, no function
+  /// or internal node nids, since those aren't expressions on their own.)
+  fn leaf(t:&str)->Option<NID> {
+    if t == "O" || t == "I" { return t.parse().ok() }
+    let mut ch = t.chars();
+    if !matches!(ch.next(), Some('x' | 'v')) { return None }
+    let digits = ch.as_str();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) { return None }
+    t.parse::<NID>().ok().filter(|n| n.is_lit()) }}
+
 impl Base for RawASTBase {
 
   fn new()->Self { RawASTBase::empty() }
@@ -264,43 +563,23 @@ impl Base for RawASTBase {
       let (lo, hi) = if x<y {(x,y)} else {(y,x)};
       self.nid(ops::vel(lo, hi)) }}
 
-  fn sub(&mut self, _v:vid::VID, _n:NID, _ctx:NID)->NID { todo!("ast::sub") }
+  fn sub(&mut self, v:vid::VID, n:NID, ctx:NID)->NID {
+    let mut cache = HashMap::new();
+    self.sub_aux(v, n, ctx, &mut cache) }
 
   fn get(&self, s:&str)->Option<NID> { Some(*self.tags.get(s)?) }
 
-  // generate dot file (graphviz)
-  fn dot(&self, n:NID, wr: &mut dyn std::fmt::Write) {
-    macro_rules! w {
-      ($x:expr $(,$xs:expr)*) => { writeln!(wr, $x $(,$xs)*).unwrap() }}
-    macro_rules! dotop {
-      ($s:expr, $n:expr $(,$xs:expr)*) => {{
-        w!("  \"{}\"[label={}];", $n.raw(), $s); // draw the node
-        $({ if ($xs).is_inv() { w!("edge[style=dashed];"); }
-            else { w!("edge[style=solid];"); }
-            w!(" \"{}\"->\"{}\";", $xs.raw(), $n.raw()); })* }}}
+  /// the number of distinct ops reachable from n.
+  fn node_count(&self, n:NID)->usize {
+    let mut c = 0; self.walk(n, &mut |x| if x.is_ixn() { c+=1 }); c }
 
-    w!("digraph bdd {{");
-    w!("  bgcolor=\"#3399cc\"; pad=0.225");
-    w!("  rankdir=BT;"); // put root on top
-    w!("  node[shape=circle, style=filled, fillcolor=\"#dddddd\", fontname=calibri];");
-    w!("  edge[style=solid]");
-    self.walk(n, &mut |n| {
-      match n {
-        nid::O => w!(" \"{}\"[label=⊥];", n),
-        nid::I => w!(" \"{}\"[label=⊤];", n),
-        _ if n.is_vid() => w!("\"{}\"[fillcolor=\"#bbbbbb\",label=\"{}\"];", n.raw(), n.vid()),
-        _ => {
-          let rpn: Vec<NID> = self.get_ops(n).to_rpn().cloned().collect();
-          let fun = rpn.last().unwrap().to_fun().unwrap();
-          if 2 == fun.arity() {
-            let (x, y) = (rpn[0], rpn[1]);
-            match fun {
-              ops::AND => dotop!("∧",n,x,y),
-              ops::XOR => dotop!("≠",n,x,y),
-              ops::VEL => dotop!("∨",n,x,y),
-              _ => panic!("unexpected op in dot(): {:?}", n) }}
-          else { panic!("can't dot arbitrary ops yet: {:?}", rpn) }}}});
-    w!("}}"); }
+  fn support(&self, n:NID)->HashSet<VID> {
+    let mut res = HashSet::new();
+    self.walk(n, &mut |x| if x.is_vid() { res.insert(x.vid()); });
+    res }
+
+  // generate dot file (graphviz)
+  fn dot(&self, n:NID, wr: &mut dyn std::fmt::Write) { self.dot_aux(n, None, wr) }
 } // impl Base for RawASTBase
 
 pub struct ASTBase { base: Simplify<RawASTBase> }
@@ -312,7 +591,7 @@ impl Default for ASTBase {
     fn default() -> Self {Self::new()}}
 
 impl Base for ASTBase {
-  inherit![when_hi, when_lo, and, xor, or, def, tag, get, sub, dot ];
+  inherit![when_hi, when_lo, and, xor, or, def, tag, get, sub, dot, node_count, support ];
   fn new()->Self { ASTBase::new() }}
 
 impl ASTBase {
@@ -322,6 +601,7 @@ impl ASTBase {
 
 test_base_consts!(ASTBase);
 test_base_when!(ASTBase);
+test_base_imp_iff!(ASTBase);
 
 #[test] fn ast_and(){
   let mut b = ASTBase::empty();
@@ -340,15 +620,45 @@ test_base_when!(ASTBase);
   assert_eq!(b.eval(and, &nid_map![x0: I, x1: O]), O, "I and O => O");
   assert_eq!(b.eval(and, &nid_map![x0: I, x1: I]), I, "I and I => I"); }
 
-// TODO: #[test] fn ast_eval_partial(){
-// (for now you have to assign all variables)
-//   nid_vars![x0, x1]; use crate::{I,O};
-//   let mut b = RawASTBase::empty();
-//   let and = expr![b, (x0 & x1)];
-//   assert_eq!(b.eval(and, &nid_map![x1: O]), O, "expect  x0 & O == O");
-//   assert_eq!(b.eval(and, &nid_map![x1: !x0]), O, "expect  x0 & ~x0 == O");
-//   assert_eq!(b.eval(and, &nid_map![x1: I]), x0, "expect x0 & I == x0");
-//   assert_eq!(b.eval(and, &nid_map![x1: x0]), x0, "expect  x0 & x0 == x0"); }
+#[test] fn ast_eval_partial(){
+  nid_vars![x0, x1]; use crate::{I,O};
+  let mut b = RawASTBase::empty();
+  let and = expr![b, (x0 & x1)];
+  assert_eq!(b.eval(and, &nid_map![x1: O]), O, "expect  x0 & O == O");
+  assert_eq!(b.eval(and, &nid_map![x1: !x0]), O, "expect  x0 & ~x0 == O");
+  assert_eq!(b.eval(and, &nid_map![x1: I]), x0, "expect x0 & I == x0");
+  assert_eq!(b.eval(and, &nid_map![x1: x0]), x0, "expect  x0 & x0 == x0"); }
+
+#[test] fn ast_eval_deep(){
+  use crate::{I,O};
+  // a chain of 50k xors would blow the stack if eval were recursive.
+  let mut b = RawASTBase::empty();
+  let mut top = NID::var(0);
+  for i in 1..50_000 { top = b.xor(top, NID::var(i % 8)); }
+  let kvs:HashMap<NID,NID> = (0..8).map(|i| (NID::var(i), if i < 3 { I } else { O })).collect();
+  let ones = (0..50_000).filter(|i| i % 8 < 3).count();
+  let expect = if ones % 2 == 1 { I } else { O };
+  assert_eq!(b.eval(top, &kvs), expect);
+  assert_eq!(b.eval(!top, &kvs), !expect);
+  // and with one more x0 on the end, the parity flips:
+  let top = b.xor(top, NID::var(0));
+  assert_eq!(b.eval(top, &kvs), !expect); }
+
+#[test] fn test_common_subexpr_stats() {
+  nid_vars![x0, x1, x2, x3];
+  let mut b = RawASTBase::empty();
+  let shared = expr![b, (x0 & x1)];
+  let l = expr![b, (shared ^ x2)];
+  let r = expr![b, (shared & x3)];
+  let top = b.xor(l, r);
+  let unused = b.and(r, x2); // not reachable from top, so it shouldn't count
+  let stats = b.common_subexpr_stats(top);
+  assert_eq!(stats.len(), 4, "only the internal nodes under top should be listed");
+  assert!(stats[&shared] >= 2, "shared subterm should have a fan-out of at least 2");
+  assert_eq!(stats[&shared], 2);
+  assert_eq!(stats[&r], 1, "parents that aren't reachable from top shouldn't count");
+  assert_eq!(stats[&top], 0);
+  assert!(!stats.contains_key(&unused)); }
 
 #[test] fn test_repack() {
   let mut b = RawASTBase::empty();
@@ -361,3 +671,140 @@ test_base_when!(ASTBase);
   assert_eq!(b2.len(), 2);
   assert_eq!(keep, vec![NID::ixn(1)]);
   assert_eq!(b2.get_ops(keep[0]), b.get_ops(xor)); }
+
+#[test] fn test_get_or_build() {
+  let mut b = RawASTBase::empty();
+  nid_vars![x0, x1];
+  let mut calls = 0;
+  let n0 = b.get_or_build("and", |b| { calls += 1; b.and(x0, x1) });
+  let n1 = b.get_or_build("and", |b| { calls += 1; b.and(x0, x1) });
+  assert_eq!(calls, 1, "builder should only run once");
+  assert_eq!(n0, n1);
+  assert_eq!(b.get("and"), Some(n0)); }
+
+#[test] fn test_cleanup() {
+  use crate::{I,O};
+  let mut b = RawASTBase::empty();
+  nid_vars![x0, x1];
+  let a = b.push_raw_ops(ops::and(x0, x0));   // x0
+  let c = b.push_raw_ops(ops::xor(a, O));     // x0
+  let d = b.push_raw_ops(ops::and(c, x1));    // x0 & x1
+  let e = b.push_raw_ops(ops::vel(x1, O));    // x1
+  b.tag(d, "d".to_string()); b.tag(e, "e".to_string()); b.tag(!c, "nc".to_string());
+  let mut b2 = b.cleanup();
+  assert_eq!(b.len(), 4);
+  assert_eq!(b2.len(), 1, "only x0 & x1 should survive");
+  for tag in ["d", "e", "nc"] {
+    let (n, n2) = (b.get(tag).unwrap(), b2.get(tag).unwrap());
+    for (v0, v1) in [(O,O), (O,I), (I,O), (I,I)] {
+      let env = nid_map![x0: v0, x1: v1];
+      assert_eq!(b.eval(n, &env), b2.eval(n2, &env), "{} differs at x0={} x1={}", tag, v0, v1); }}}
+
+#[test] fn test_ast_sub() {
+  let mut b = RawASTBase::empty();
+  nid_vars![x0, x1, x2, x3];
+  let ctx = b.and(x0, x1);
+  let x23 = b.and(x2, x3);
+  let res = b.sub(x0.vid(), x23, ctx);
+  assert_eq!(b.get_ops(res), &ops::and(x23, x1));
+  // inverted nids on either side of the substitution:
+  assert_eq!(b.sub(x0.vid(), x23, !ctx), !res);
+  let ctx = b.and(!x0, x1);
+  let res = b.sub(x0.vid(), x23, ctx);
+  assert_eq!(b.get_ops(res), &ops::and(x1, !x23)); // (and() put x1 first)
+  // contexts that don't mention v come back unchanged:
+  assert_eq!(b.sub(x3.vid(), x0, ctx), ctx);}
+
+#[test] fn test_ast_to_sexpr() {
+  let mut b = RawASTBase::empty();
+  nid_vars![x0, x1, x2];
+  let n = expr![b, (x0 & (x1 ^ x2))];
+  assert_eq!(b.to_sexpr(n), "(x1 % x2) * x0"); // and() sorts its arguments
+  let x01 = b.and(x0, x1); let n = b.or(x01, !x2);
+  assert_eq!(b.to_sexpr(n), "(x0 * x1) + ~x2");
+  assert_eq!(b.to_sexpr(!x01), "~(x0 * x1)");}
+
+#[test] fn test_ast_parse() {
+  use crate::{I,O};
+  let mut b = RawASTBase::empty();
+  nid_vars![x0, x1, x2];
+  let x01 = b.and(x0, x1);
+  let expect = b.or(x01, !x2);
+  assert_eq!(b.parse("x0 * x1 + ~x2"), Ok(expect));
+  assert_eq!(b.parse("(x0*x1) + (~x2)"), Ok(expect));
+  let x12 = b.xor(x1, x2); let expect = b.and(x0, x12);
+  assert_eq!(b.parse("x0 * (x1 % x2)"), Ok(expect));
+  assert_eq!(b.parse("x0 = x0"), Ok(I));
+  assert_eq!(b.parse("x0 < x0"), Ok(O));
+  assert_eq!(b.parse("I ? x1 : x2"), Ok(x1));
+  let n = b.parse("x0 ? x1 : x2").unwrap();
+  for (v0, v1, v2) in [(O,O,I), (O,I,O), (I,O,I), (I,I,O)] {
+    let env = nid_map![x0: v0, x1: v1, x2: v2];
+    assert_eq!(b.eval(n, &env), if v0 == I { v1 } else { v2 }); }
+  assert!(b.parse("x0 *").is_err());
+  assert!(b.parse("(x0 * x1").is_err());
+  assert!(b.parse("x0 x1").is_err());
+  assert!(b.parse("x0 & x1").is_err());}
+
+#[test] fn test_ast_parse_imp() {
+  use crate::{simp, nid::{I, O}};
+  let mut b = RawASTBase::empty();
+  let (x0, x1) = (NID::var(0), NID::var(1));
+  // whenever simp::imp knows the answer, the parser should agree:
+  let args = [("O", O), ("I", I), ("x0", x0), ("~x0", !x0), ("x1", x1)];
+  for (xs, x) in args { for (ys, y) in args {
+    if let Some(expect) = simp::imp(x, y) {
+      assert_eq!(b.parse(&format!("{} / {}", xs, ys)), Ok(expect), "{} / {}", xs, ys) }}}
+  // otherwise it's ~x + y:
+  let expect = b.or(!x0, x1);
+  assert_eq!(b.parse("x0 / x1"), Ok(expect));
+  assert_eq!(b.parse("~x1 + x0 / x1"), b.parse("(~x1 + x0) / x1"), "/ binds looser than +");
+  let expect = b.parse("x0 / (x1 / x2)");
+  assert_eq!(b.parse("x0 / x1 / x2"), expect, "/ groups to the right"); }
+
+#[test] fn test_ast_parse_leaves() {
+  let mut b = RawASTBase::empty();
+  assert_eq!(b.parse("vA * x1F"), Ok(b.and(NID::vir(10), NID::var(31))));
+  for bad in ["t1110 * x0", "x0 * f9.FF", "#0", "x0 + #1", "x0.1", "!x0", "xg", "x", "y0"] {
+    assert!(b.parse(bad).is_err(), "{:?} should not parse", bad) }}
+
+#[test] fn test_ast_parse_depth() {
+  let mut b = RawASTBase::empty();
+  let nest = |n:usize| format!("{}x0{}", "(".repeat(n), ")".repeat(n));
+  assert_eq!(b.parse(&nest(MAX_PARSE_DEPTH)), Ok(NID::var(0)));
+  assert_eq!(b.parse(&nest(MAX_PARSE_DEPTH+1)), Err("too deeply nested".to_string()));
+  assert_eq!(b.parse(&nest(10_000)), Err("too deeply nested".to_string()));
+  let ites = "x0 ? x1 : ".repeat(10_000) + "x2";
+  assert_eq!(b.parse(&ites), Err("too deeply nested".to_string()));
+  // unary and binary chains don't nest, so these shouldn't overflow either:
+  assert_eq!(b.parse(&("~".repeat(10_001) + "x0")), Ok(!NID::var(0)));
+  let imps = "x0 / ".repeat(10_000) + "x1";
+  assert!(b.parse(&imps).is_ok()); }
+
+#[test] fn test_ast_dot_by_cost() {
+  nid_vars![x0, x1, x2];
+  let mut b = RawASTBase::empty();
+  let top = expr![b, (((x0 & x1) ^ x2) & x0)];
+  let fill_of = |s:&str, n:NID| s.lines().find(|l| l.contains(&format!("\"{}\"[label", n)))
+    .and_then(|l| l.split("fillcolor=").nth(1)).map(|c| c.to_string());
+  let mut s = String::new(); b.dot_by_cost(top, &mut s);
+  let (cheap, dear) = (fill_of(&s, x2), fill_of(&s, top));
+  assert!(cheap.is_some() && dear.is_some(), "every node should get a fillcolor:\n{}", s);
+  assert_ne!(cheap, dear, "the root should be shaded differently from a leaf");
+  assert!(dear.unwrap().starts_with("\"#cc2222\""), "the most expensive node is red");
+  let mut plain = String::new(); b.dot(top, &mut plain);
+  assert_eq!(fill_of(&plain, top), None, "plain dot() leaves ops with the default fill"); }
+
+#[test] fn test_ast_op_histogram() {
+  nid_vars![x0, x1, x2, x3];
+  let mut b = RawASTBase::empty();
+  let a = expr![b, (x0 & x1)];
+  let o = b.or(x2, x3);
+  let top = expr![b, ((a ^ o) & (a ^ x2))];
+  let _unused = b.or(top, x1);
+  let h = b.op_histogram(top);
+  assert_eq!(h.get(&ops::AND), Some(&2), "x0&x1 is shared, so it only counts once");
+  assert_eq!(h.get(&ops::XOR), Some(&2));
+  assert_eq!(h.get(&ops::VEL), Some(&1), "nodes that aren't reachable don't count");
+  assert_eq!(h.values().sum::<usize>(), 5);
+  assert!(b.op_histogram(x0).is_empty()); }
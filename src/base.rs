@@ -3,7 +3,7 @@
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::Write;
-use std::process::Command;      // for creating and viewing digarams
+use std::process::{Command, Stdio};  // for creating and viewing digarams
 use crate::{simp, nid::NID};
 use crate::vid::VID;
 use crate::reg::Reg;
@@ -27,6 +27,14 @@ pub trait Base {
   /// Return a `NID` representing the logical OR of `x` and `y`.
   fn or(&mut self, x:NID, y:NID)->NID;
 
+  /// Return a `NID` representing the implication `x → y` (that is, `!x ∨ y`).
+  fn imp(&mut self, x:NID, y:NID)->NID {
+    if let Some(nid) = simp::imp(x,y) { nid } else { self.or(!x, y) }}
+
+  /// Return a `NID` representing the equivalence `x ↔ y` (that is, `¬(x ⊕ y)`).
+  fn iff(&mut self, x:NID, y:NID)->NID {
+    if let Some(nid) = simp::eq(x,y) { nid } else { !self.xor(x, y) }}
+
   /// Assign a name to variable `v`, and return its `NID`.
   fn def(&mut self, s:String, v:VID)->NID;
 
@@ -47,6 +55,14 @@ pub trait Base {
   // TODO: figure out the right way to return an iterator in a trait.
   fn solution_set(&self, _n:NID, _nvars:usize)->HashSet<Reg> { unimplemented!() }
 
+  /// Return the number of distinct nodes reachable from `n` (including `n` itself).
+  /// (There's no default, since walking the nodes depends on how each base stores them.)
+  fn node_count(&self, n:NID)->usize;
+
+  /// Return the set of input variables that node `n` depends on.
+  /// (Like node_count, this has to walk the nodes, so each base provides its own.)
+  fn support(&self, n:NID)->HashSet<VID>;
+
   // !! these are defined here but never overwritten in the trait (used by solver) [fix this]
   fn init_stats(&mut self) { }
   fn print_stats(&mut self) { }}
@@ -62,19 +78,33 @@ pub trait GraphViz {
     let mut txt = File::create(path).expect("couldn't create dot file");
     txt.write_all(s.as_bytes()).expect("failed to write text to dot file"); }
 
+  /// run graphviz on the diagram for n, and return the output in the given format
+  /// (anything `dot -T` accepts, like "svg" or "png"). The `dot` binary can be
+  /// overridden with the `BEX_DOT` environment variable.
+  fn render(&self, n:NID, format:&str)->std::io::Result<Vec<u8>> {
+    let mut s = String::new(); self.write_dot(n, &mut s);
+    let mut child = Command::new(dot_path()).arg(format!("-T{}", format))
+      .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    child.stdin.take().expect("no stdin for dot").write_all(s.as_bytes())?;
+    let out = child.wait_with_output()?;
+    if out.status.success() { Ok(out.stdout) }
+    else { Err(std::io::Error::other(format!("dot failed: {}", String::from_utf8_lossy(&out.stderr).trim()))) }}
+
   /// call save_dot, use graphviz to convert to svg, and open result in firefox
   fn show_named(&self, n:NID, s:&str) {
     self.save_dot(n, format!("{}.dot", s).as_str());
-    let out = Command::new("dot").args(["-Tsvg",format!("{}.dot",s).as_str()])
-      .output().expect("failed to run 'dot' command");
+    let out = self.render(n, "svg").expect("failed to run 'dot' command");
     let mut svg = File::create(format!("{}.svg",s).as_str()).expect("couldn't create svg");
-    svg.write_all(&out.stdout).expect("couldn't write svg");
+    svg.write_all(&out).expect("couldn't write svg");
     let _ = Command::new("firefox").args([format!("{}.svg",s).as_str()])
       .spawn().expect("failed to launch firefox").wait(); }
 
   fn show(&self, n:NID) { self.show_named(n, "+bdd") }
 }
 
+/// path to the graphviz `dot` binary (from `BEX_DOT`, or just "dot" to search the path).
+fn dot_path()->String { std::env::var("BEX_DOT").unwrap_or_else(|_| "dot".to_string()) }
+
 impl<T:Base> GraphViz for T {
   fn write_dot(&self, n:NID, wr: &mut dyn std::fmt::Write) {
     T::dot(self,n, wr)}}
@@ -91,7 +121,7 @@ impl<T:Base> GraphViz for T {
 /// // example do-nothing decorator
 /// pub struct Decorated<T:Base> { base: T }
 /// impl<T:Base> Base for Decorated<T> {
-///   inherit![ new, when_hi, when_lo, and, xor, or, def, tag, get, sub, dot ]; }
+///   inherit![ new, when_hi, when_lo, and, xor, or, def, tag, get, sub, dot, node_count, support ]; }
 /// ```
 #[macro_export] macro_rules! inherit {
   ( $($i:ident),* ) => { $( inherit!(@fn $i); )* };
@@ -101,11 +131,15 @@ impl<T:Base> GraphViz for T {
   (@fn and) =>      { #[inline] fn and(&mut self, x:NID, y:NID)->NID { self.base.and(x, y) }};
   (@fn xor) =>      { #[inline] fn xor(&mut self, x:NID, y:NID)->NID { self.base.xor(x, y) }};
   (@fn or) =>       { #[inline] fn or(&mut self, x:NID, y:NID)->NID  { self.base.or(x, y) }};
+  (@fn imp) =>      { #[inline] fn imp(&mut self, x:NID, y:NID)->NID { self.base.imp(x, y) }};
+  (@fn iff) =>      { #[inline] fn iff(&mut self, x:NID, y:NID)->NID { self.base.iff(x, y) }};
   (@fn def) =>      { #[inline] fn def(&mut self, s:String, i:VID)->NID { self.base.def(s, i) }};
   (@fn tag) =>      { #[inline] fn tag(&mut self, n:NID, s:String)->NID { self.base.tag(n, s) }};
   (@fn get) =>      { #[inline] fn get(&self, s:&str)->Option<NID> { self.base.get(s) }};
   (@fn sub) =>      { #[inline] fn sub(&mut self, v:VID, n:NID, ctx:NID)->NID { self.base.sub(v, n, ctx) }};
-  (@fn dot) =>      { #[inline] fn dot(&self, n:NID, wr: &mut dyn std::fmt::Write) { self.base.dot(n, wr) }}; }
+  (@fn dot) =>      { #[inline] fn dot(&self, n:NID, wr: &mut dyn std::fmt::Write) { self.base.dot(n, wr) }};
+  (@fn node_count) => { #[inline] fn node_count(&self, n:NID)->usize { self.base.node_count(n) }};
+  (@fn support) =>  { #[inline] fn support(&self, n:NID)->std::collections::HashSet<VID> { self.base.support(n) }}; }
 
 
 
@@ -113,7 +147,7 @@ impl<T:Base> GraphViz for T {
 pub struct Simplify<T:Base> { pub base: T }
 
 impl<T:Base> Base for Simplify<T> {
-  inherit![ new, when_hi, when_lo, xor, or, def, tag, get, sub, dot ];
+  inherit![ new, when_hi, when_lo, xor, or, imp, iff, def, tag, get, sub, dot, node_count, support ];
   fn and(&mut self, x:NID, y:NID)->NID {
     if let Some(nid) = simp::and(x,y) { nid }
     else {
@@ -217,7 +251,59 @@ base_test!(test_base_when, b, {
   assert_eq!(b.when_hi(vx1, x1), I, "when_hi(vx1, x1) should be I");
 });
 
+// Test the derived operators imp and iff.
+base_test!(test_base_imp_iff, b, {
+  use crate::nid::{O,I};
+  nid_vars![x0, x1];
+  assert_eq!(b.iff(x0, x0), I, "x0 ↔ x0");
+  assert_eq!(b.iff(x0, !x0), O, "x0 ↔ ¬x0");
+  assert_eq!(b.imp(I, x0), x0, "I → x0");
+  assert_eq!(b.imp(O, x0), I, "O → x0");
+  let (nx0, x01) = (!x0, b.xor(x0, x1));
+  assert_eq!(b.imp(x0, x1), b.or(nx0, x1), "x0 → x1");
+  assert_eq!(b.iff(x0, x1), !x01, "x0 ↔ x1"); });
+
+/// node_count should agree across backends that share a node structure.
+#[test] fn test_node_count() {
+  use crate::{bdd::BddBase, anf::ANFBase, ast::ASTBase};
+  fn and3<B:Base>(b:&mut B)->NID {
+    nid_vars![x0, x1, x2];
+    expr![b, ((x0 & x1) & x2)] }
+  let mut bdd = BddBase::new(); let n = and3(&mut bdd);
+  assert_eq!(Base::node_count(&bdd, n), 3, "bdd: x2 -> x1 -> x0");
+  let mut anf = ANFBase::new(); let n = and3(&mut anf);
+  assert_eq!(anf.node_count(n), 3, "anf: x2 -> x1 -> x0");
+  let mut ast = ASTBase::new(); let n = and3(&mut ast);
+  assert_eq!(ast.node_count(n), 2, "ast: two 'and' ops");
+  assert_eq!(anf.node_count(crate::nid::O), 0); }
+
+/// support should be the same set of input variables in every backend.
+#[test] fn test_support() {
+  use crate::{bdd::BddBase, anf::ANFBase, ast::ASTBase, nid::O};
+  fn check<B:Base>(b:&mut B, name:&str) {
+    nid_vars![x0, x1, x2];
+    let n = expr![b, (x0 & x1)];
+    let expect:HashSet<VID> = [x0.vid(), x1.vid()].into_iter().collect();
+    assert_eq!(Base::support(b, n), expect, "{name}: support of x0 & x1");
+    let n = expr![b, (x2 ^ x0)];
+    let expect:HashSet<VID> = [x0.vid(), x2.vid()].into_iter().collect();
+    assert_eq!(Base::support(b, !n), expect, "{name}: support of !(x2 ^ x0)");
+    assert!(Base::support(b, O).is_empty(), "{name}: support of O"); }
+  check(&mut BddBase::new(), "bdd");
+  check(&mut ANFBase::new(), "anf");
+  check(&mut ASTBase::new(), "ast"); }
+
 
+/// render() should hand back graphviz output (when graphviz is installed).
+#[test] fn test_render() {
+  use crate::bdd::BddBase;
+  if Command::new(dot_path()).arg("-V").output().is_err() { return } // no graphviz
+  let mut b = BddBase::new();
+  nid_vars![x0, x1];
+  let n = b.and(x0, x1);
+  let svg = b.render(n, "svg").expect("render failed");
+  assert!(svg.starts_with(b"<?xml") || svg.starts_with(b"<svg"), "expected svg output");
+  assert!(b.render(n, "no-such-format").is_err()); }
 
 // TODO: put these elsewhere.
 // pub fn order<T:PartialOrd>(x:T, y:T)->(T,T) { if x < y { (x,y) } else { (y,x) }}
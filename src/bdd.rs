@@ -1,12 +1,17 @@
 //! A module for efficient implementation of binary decision diagrams.
 extern crate num_cpus;
 use std::collections::{HashMap,HashSet};
+use std::io;
+use serde::{Serialize, Deserialize};
 use crate::base::Base;
 use crate::reg::Reg;
-use crate::vhl::Walkable;
-use crate::nid::{NID,O,I};
-use crate::vid::{VID,VidOrdering,topmost_of3};
+use crate::vhl::{HiLo, Walkable};
+use crate::nid::{NID,O,I,NidFun};
+use crate::fun::Fun;
+use crate::vid::{VID,VidOrdering,VidOrder,ReorderError,topmost,topmost_of3,botmost};
 use crate::wip;
+use crate::apl;
+use crate::io::{write_versioned, read_versioned};
 
 mod bdd_sols;
 pub mod bdd_swarm; use self::bdd_swarm::*;
@@ -93,19 +98,75 @@ impl ITE {
             else { return Norm::Ite(NormIteKey(ITE::new(f,g,h))) }}}}}} }
 
 
+/// format version for files written by `BddBase::save`. (see `io::write_versioned`)
+pub const BDD_FORMAT_VERSION:u32 = 1;
+
+/// The part of a BddBase that gets written to disk. (The memo cache and
+/// the worker threads are rebuilt from scratch on load.)
+#[derive(Serialize, Deserialize)]
+struct BddSaveData { tags: HashMap<String, NID>, hilos: Vec<HiLo> }
+
+/// Colors, font, and shapes used when rendering a bdd with `BddBase::dot_styled`.
+/// The default is the style that `dot` has always used.
+#[derive(Debug, Clone)]
+pub struct DotStyle {
+  /// background color of the whole diagram
+  pub bgcolor: String,
+  /// fill color for the branch nodes
+  pub node_fill: String,
+  /// font for all the labels
+  pub font: String,
+  /// graphviz shape for the I and O nodes
+  pub terminal_shape: String }
+
+impl Default for DotStyle {
+  fn default()->Self {
+    DotStyle {
+      bgcolor: "#3399cc".to_string(), node_fill: "#bbbbbb".to_string(),
+      font: "calibri".to_string(), terminal_shape: "square".to_string() }}}
+
+/// Snapshot of the cache statistics for a `BddBase` (see `BddBase::stats`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+  /// number of cache lookups made by the workers
+  pub tests: u64,
+  /// number of those lookups that found a finished result
+  pub hits: u64,
+  /// number of nodes in the node table
+  pub node_count: usize,
+  /// number of entries currently in the swarm's memo cache
+  pub memo_entries: usize }
+
+impl std::fmt::Display for CacheStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>)->std::fmt::Result {
+    let pct = if self.tests == 0 { 0.0 } else { (self.hits as f64/self.tests as f64) * 100.0 };
+    write!(f, "Cache stats: {} hits / {} tests ({:.1}%), {} nodes, {} memo entries.",
+      self.hits, self.tests, pct, self.node_count, self.memo_entries) }}
+
+/// escape a string for use inside a quoted graphviz label.
+fn dot_escape(s:&str)->String { s.replace('\\', "\\\\").replace('"', "\\\"") }
+
 /// Finally, we put everything together. This is the top-level type for this crate.
 #[derive(Debug)]
 pub struct BddBase {
   /// allows us to give user-friendly names to specific nodes in the base.
   pub tags: HashMap<String, NID>,
+  /// optional log of (f, g, h) -> nid for each call to ite()
+  op_log: Option<Vec<(NID,NID,NID,NID)>>,
+  /// cache for compose(): (ctx, v, g) -> result
+  compose_memo: HashMap<(NID,VID,NID),NID>,
   pub swarm: BddSwarm} // TODO: nopub
 
 impl BddBase {
 
-  pub fn new()->BddBase { BddBase{swarm: BddSwarm::new(), tags:HashMap::new()}}
+  pub fn new()->BddBase { BddBase{swarm: BddSwarm::new(), tags:HashMap::new(), op_log:None, compose_memo:HashMap::new()}}
 
+  /// create a base whose swarm has n worker threads. (n=0 means one per cpu.)
   pub fn new_with_threads(n:usize)->BddBase {
-    BddBase{swarm: BddSwarm::new_with_threads(n), tags:HashMap::new()}}
+    BddBase{swarm: BddSwarm::new_with_threads(n), tags:HashMap::new(), op_log:None, compose_memo:HashMap::new()}}
+
+  /// number of worker threads used by this base.
+  pub fn thread_count(&self)->usize { self.swarm.num_workers() }
 
   /// return (hi, lo) pair for the given nid. used internally
   #[inline] fn tup(&self, n:NID)->(NID,NID) { self.swarm.tup(n) }
@@ -114,7 +175,48 @@ impl BddBase {
     let (hi, lo) = self.tup(n); (n.vid(), hi, lo) }
 
   // clear all data from the cache (mostly for benchmarks)
-  pub fn reset(&mut self) { self.swarm.reset(); }
+  pub fn reset(&mut self) { self.swarm.reset(); self.compose_memo.clear(); }
+
+  /// limit the number of entries in the swarm's memo cache (None means no limit).
+  /// Once the limit is passed, the finished entries are evicted after the current operation.
+  /// This only affects speed and memory use: the node table itself is never evicted.
+  pub fn set_cache_limit(&mut self, n:Option<usize>) { self.swarm.set_cache_limit(n) }
+
+  /// number of nodes in the node table
+  pub fn len(&self)->usize { self.swarm.hilos().len() }
+  pub fn is_empty(&self)->bool { self.swarm.hilos().is_empty() }
+
+  /// garbage collection: compact the node table down to the nodes reachable from
+  /// the keep roots. Since this renumbers the nodes, it returns a map from old nids
+  /// to new ones. (Keys are the kept roots plus every non-inverted node under them.)
+  /// Tags pointing at nodes that didn't survive are dropped, and the caches and
+  /// op log are cleared.
+  pub fn gc(&mut self, keep:&[NID])->HashMap<NID,NID> {
+    let mut map:HashMap<NID,NID> = HashMap::new();
+    let mut hilos:Vec<HiLo> = vec![];
+    // hi/lo pairs are shared across variables, so each distinct pair gets one slot:
+    let mut index:HashMap<HiLo,usize> = HashMap::new();
+    let nn = |x:NID, map:&HashMap<NID,NID>| {
+      if x.is_const() { x } else { let r = map[&x.raw()]; if x.is_inv() { !r } else { r }}};
+    for &root in keep {
+      if root.is_const() { continue }
+      // walk_up visits children first, so they're always mapped before their parents.
+      self.walk_up(root, &mut |x, v, _, _| {
+        let x = x.raw();
+        if map.contains_key(&x) { return }
+        if x.is_vid() { map.insert(x, x); return }
+        let (hi, lo) = self.tup(x);
+        let hilo = HiLo{ hi:nn(hi, &map), lo:nn(lo, &map) };
+        let ix = *index.entry(hilo).or_insert_with(|| { hilos.push(hilo); hilos.len()-1 });
+        map.insert(x, NID::from_vid_idx(v, ix)); })}
+    self.reset();
+    for hilo in hilos { self.swarm.hilos().push(hilo); }
+    if let Some(log) = self.op_log.as_mut() { log.clear() }
+    self.tags = self.tags.iter()
+      .filter(|(_, &n)| n.is_const() || map.contains_key(&n.raw()))
+      .map(|(k, &n)| (k.clone(), nn(n, &map))).collect();
+    for &root in keep { map.insert(root, nn(root, &map)); }
+    map }
 
 
   // public node constructors
@@ -123,9 +225,267 @@ impl BddBase {
   pub fn  lt(&mut self, x:NID, y:NID)->NID { self.ite(x, O, y) }
 
   /// all-purpose node creation/lookup
-  #[inline] pub fn ite(&mut self, f:NID, g:NID, h:NID)->NID { self.swarm.ite(f,g,h) }
+  #[inline] pub fn ite(&mut self, f:NID, g:NID, h:NID)->NID {
+    let res = self.swarm.ite(f,g,h);
+    if let Some(log) = self.op_log.as_mut() { log.push((f, g, h, res)) }
+    res }
+
+  /// start recording each call to ite() as an (f, g, h, result) tuple.
+  /// Replaying the log with ite() on a fresh base rebuilds the same node table,
+  /// provided both bases use a single worker thread. (With more threads, the
+  /// order in which nodes get their indices depends on scheduling.)
+  pub fn enable_op_log(&mut self) { if self.op_log.is_none() { self.op_log = Some(vec![]) }}
+
+  /// the operations recorded since enable_op_log() was called.
+  pub fn op_log(&self)->&[(NID,NID,NID,NID)] {
+    match &self.op_log { Some(log) => log, None => &[] }}
+
+  /// iterate through the (variable-agnostic) hi/lo pairs in the node table, in index order.
+  pub fn iter_nodes(&self)->impl Iterator<Item=HiLo> + '_ { self.swarm.hilos().iter() }
+
+  /// threshold function: true when at least k of the given inputs are true.
+  /// Builds the bdd bottom-up from a table where row[c] means "at least c of
+  /// the inputs seen so far are true", so the result has O(n·k) nodes.
+  pub fn atleast(&mut self, vars:&[NID], k:usize)->NID {
+    if k == 0 { return I }
+    if k > vars.len() { return O }
+    let mut vs = vars.to_vec();
+    vs.sort_by_key(|n| std::cmp::Reverse(n.vid())); // bottom var first
+    let mut row = vec![O; k+1]; row[0] = I;
+    for v in vs {
+      for c in (1..=k).rev() { row[c] = self.ite(v, row[c-1], row[c]) }}
+    row[k] }
+
+  /// threshold function: true when at most k of the given inputs are true.
+  pub fn atmost(&mut self, vars:&[NID], k:usize)->NID { !self.atleast(vars, k+1) }
+
+  /// true when exactly k of the given inputs are true.
+  pub fn exactly(&mut self, vars:&[NID], k:usize)->NID {
+    let (lo, hi) = (self.atleast(vars, k), self.atmost(vars, k));
+    self.and(lo, hi) }
+
+  /// one-hot constraint for a categorical variable: exactly one of the inputs is true.
+  pub fn onehot(&mut self, vars:&[NID])->NID { self.exactly(vars, 1) }
+
+  /// given a solution and the register positions of a one-hot group, return
+  /// the index (within the group) of the hot bit, or None if the group isn't one-hot.
+  pub fn decode_onehot(reg:&Reg, group:&[usize])->Option<usize> {
+    let mut hot = group.iter().enumerate().filter(|(_, &ix)| reg.get(ix)).map(|(i, _)| i);
+    match (hot.next(), hot.next()) { (Some(i), None) => Some(i), _ => None }}
+
+  /// unsigned comparison of two bit vectors (least significant bit first): a < b
+  pub fn ult(&mut self, a:&[NID], b:&[NID])->NID {
+    assert_eq!(a.len(), b.len(), "ult: bit vectors must have the same length");
+    // walk down from the most significant bit, tracking "equal so far"
+    let (mut lt, mut eq) = (O, I);
+    for (&x, &y) in a.iter().zip(b.iter()).rev() {
+      let here = self.and(!x, y);
+      let now = self.and(eq, here);
+      lt = self.or(lt, now);
+      let same = self.xor(x, !y);
+      eq = self.and(eq, same); }
+    lt }
+
+  /// unsigned comparison of two bit vectors (least significant bit first): a <= b
+  pub fn ule(&mut self, a:&[NID], b:&[NID])->NID { !self.ult(b, a) }
+
+  /// equality of two bit vectors
+  pub fn ueq(&mut self, a:&[NID], b:&[NID])->NID {
+    assert_eq!(a.len(), b.len(), "ueq: bit vectors must have the same length");
+    let mut eq = I;
+    for (&x, &y) in a.iter().zip(b.iter()) {
+      let same = self.xor(x, !y);
+      eq = self.and(eq, same); }
+    eq }
 
 
+  /// boolean division by a variable: returns (quotient, remainder) such that
+  /// n = (v ∧ q) ∨ r, where neither q nor r depends on v. This only works when
+  /// n is positive unate in v (that is, when n|v=0 implies n|v=1), so we panic otherwise.
+  pub fn divide_by_var(&mut self, n:NID, v:VID)->(NID, NID) {
+    let (hi, lo) = (self.when_hi(v, n), self.when_lo(v, n));
+    if self.and(lo, !hi) != O {
+      panic!("divide_by_var: {} is not positive unate in {}", n, v) }
+    (hi, lo) }
+
+  /// do x and y represent the same function? Within a single base, this is just
+  /// `x == y`, but it's handy to have a name for it when x and y were built in
+  /// different ways (for example, after reordering the inputs and putting them back).
+  pub fn equiv(&mut self, x:NID, y:NID)->bool { self.xor(x, y) == O }
+
+  /// do x and y agree everywhere inside the care set?
+  pub fn equiv_under(&mut self, x:NID, y:NID, care:NID)->bool {
+    let diff = self.xor(x, y);
+    self.and(diff, care) == O }
+
+  /// Coudert and Madre's `restrict` operator: returns a (usually smaller) bdd that
+  /// agrees with onset wherever care is true, using the don't-care inputs (where
+  /// care is false) to merge branches.
+  pub fn restrict_dc(&mut self, onset:NID, care:NID)->NID {
+    let mut memo = HashMap::new();
+    self.restrict_aux(onset, care, &mut memo) }
+
+  fn restrict_aux(&mut self, f:NID, c:NID, memo:&mut HashMap<(NID,NID),NID>)->NID {
+    if c == O || c == I || f.is_const() { return f }
+    if f == c { return I }
+    if f == !c { return O }
+    if let Some(&n) = memo.get(&(f,c)) { return n }
+    let cv = c.vid();
+    let (c1, c0) = (self.when_hi(cv, c), self.when_lo(cv, c));
+    let res =
+      if c0 == O { let f1 = self.when_hi(cv, f); self.restrict_aux(f1, c1, memo) }
+      else if c1 == O { let f0 = self.when_lo(cv, f); self.restrict_aux(f0, c0, memo) }
+      else if cv.is_above(&f.vid()) {
+        // f doesn't branch on cv, so merge both halves of the care set
+        let c2 = self.or(c1, c0);
+        self.restrict_aux(f, c2, memo) }
+      else {
+        let v = f.vid(); // at or above cv
+        let (f1, f0) = (self.when_hi(v, f), self.when_lo(v, f));
+        let (c1, c0) = (self.when_hi(v, c), self.when_lo(v, c));
+        let hi = self.restrict_aux(f1, c1, memo);
+        let lo = self.restrict_aux(f0, c0, memo);
+        self.ite(NID::from_vid(v), hi, lo) };
+    memo.insert((f,c), res);
+    res }
+
+  /// existential quantification: ∃vars.n (the OR of the cofactors for each var in the set)
+  pub fn exists(&mut self, vars:&HashSet<VID>, n:NID)->NID {
+    self.quantify(vars, n, true) }
+
+  /// universal quantification: ∀vars.n (the AND of the cofactors for each var in the set)
+  pub fn forall(&mut self, vars:&HashSet<VID>, n:NID)->NID {
+    self.quantify(vars, n, false) }
+
+  /// count the assignments to the variables with levels (var indices) in lo..=hi that can
+  /// be extended to a solution of n. Variables outside the window are treated as free
+  /// (existentially quantified), so this is the model count of n projected onto the window.
+  pub fn windowed_count(&mut self, n:NID, lo:u32, hi:u32)->u64 {
+    assert!(lo <= hi, "windowed_count: empty window [{}, {}]", lo, hi);
+    let outside:HashSet<VID> = self.support(n).into_iter()
+      .filter(|v| v.var_ix() < lo as usize || v.var_ix() > hi as usize).collect();
+    let proj = self.exists(&outside, n);
+    // solution_count also counts the (free) variables below the window, so divide those out.
+    self.solution_count(proj, hi as usize + 1) >> lo }
+
+  /// count the solutions of n whose value, reading vars as an unsigned integer
+  /// (least significant bit first, as in ult()), is in the range lo..hi.
+  /// The count is over the first k input variables, where k is just enough to
+  /// cover both the vars and the support of n.
+  pub fn count_in_range(&mut self, n:NID, vars:&[VID], lo:u64, hi:u64)->u64 {
+    assert!(vars.len() < 64, "count_in_range only works for < 64 variables");
+    if lo >= hi { return 0 }
+    let xs:Vec<NID> = vars.iter().map(|&v| NID::from_vid(v)).collect();
+    // x < k, as a bdd. (k = 2^len is past the largest value, so anything is below it.)
+    let below = |base:&mut BddBase, k:u64|->NID {
+      if k >> xs.len() > 0 { return I }
+      let ks:Vec<NID> = (0..xs.len()).map(|i| if (k >> i) & 1 == 1 { I } else { O }).collect();
+      base.ult(&xs, &ks) };
+    let (top, bot) = (below(self, hi), below(self, lo));
+    let range = self.and(top, !bot);
+    let res = self.and(n, range);
+    let nvars = self.support(n).into_iter().chain(vars.iter().copied())
+      .map(|v| v.var_ix()+1).max().unwrap_or(0);
+    self.solution_count(res, nvars) }
+
+  /// shared implementation of exists/forall
+  fn quantify(&mut self, vars:&HashSet<VID>, n:NID, exists:bool)->NID {
+    let bot = if let Some(b) = vars.iter().cloned().reduce(botmost) { b } else { return n };
+    let mut memo = HashMap::new();
+    self.quantify_aux(vars, bot, n, exists, &mut memo) }
+
+  fn quantify_aux(&mut self, vars:&HashSet<VID>, bot:VID, n:NID, exists:bool,
+                  memo:&mut HashMap<NID,NID>)->NID {
+    // nothing to do if n is constant or lies entirely below the quantified vars
+    if n.is_const() || n.vid().is_below(&bot) { return n }
+    if let Some(&res) = memo.get(&n) { return res }
+    let v = n.vid(); let (hi, lo) = self.tup(n);
+    let hi = self.quantify_aux(vars, bot, hi, exists, memo);
+    let res =
+      if vars.contains(&v) {
+        // short circuit when the first cofactor already decides the answer
+        let done = if exists { I } else { O };
+        if hi == done { done } else {
+          let lo = self.quantify_aux(vars, bot, lo, exists, memo);
+          if exists { self.or(hi, lo) } else { self.and(hi, lo) }}}
+      else {
+        let lo = self.quantify_aux(vars, bot, lo, exists, memo);
+        self.ite(NID::from_vid(v), hi, lo) };
+    memo.insert(n, res);
+    res }
+
+  /// cofactor n with respect to several variables at once (a partial assignment).
+  /// Variables not mentioned in the assignment are left alone.
+  pub fn restrict(&mut self, n:NID, assignment:&HashMap<VID,bool>)->NID {
+    let bot = if let Some(b) = assignment.keys().cloned().reduce(botmost) { b } else { return n };
+    let mut memo = HashMap::new();
+    self.restrict_vars_aux(n, assignment, bot, &mut memo) }
+
+  fn restrict_vars_aux(&mut self, n:NID, assignment:&HashMap<VID,bool>, bot:VID,
+                       memo:&mut HashMap<NID,NID>)->NID {
+    if n.is_const() || n.vid().is_below(&bot) { return n }
+    if let Some(&res) = memo.get(&n) { return res }
+    let v = n.vid(); let (hi, lo) = self.tup(n);
+    let res = match assignment.get(&v) {
+      Some(&val) => self.restrict_vars_aux(if val { hi } else { lo }, assignment, bot, memo),
+      None => {
+        let hi = self.restrict_vars_aux(hi, assignment, bot, memo);
+        let lo = self.restrict_vars_aux(lo, assignment, bot, memo);
+        self.ite(NID::from_vid(v), hi, lo) }};
+    memo.insert(n, res);
+    res }
+
+  /// relabel the input variables of n according to perm (which should be a bijection).
+  /// That is, the result is n with each var v replaced by perm[v]. Vars not in perm are left alone.
+  pub fn permute_inputs(&mut self, n:NID, perm:&HashMap<VID,VID>)->NID {
+    let mut memo = HashMap::new();
+    self.permute_aux(n, perm, &mut memo) }
+
+  fn permute_aux(&mut self, n:NID, perm:&HashMap<VID,VID>, memo:&mut HashMap<NID,NID>)->NID {
+    if n.is_const() { return n }
+    if let Some(&res) = memo.get(&n) { return res }
+    let v = n.vid(); let (hi, lo) = self.tup(n);
+    let (hi, lo) = (self.permute_aux(hi, perm, memo), self.permute_aux(lo, perm, memo));
+    let pv = *perm.get(&v).unwrap_or(&v);
+    let res = self.ite(NID::from_vid(pv), hi, lo);
+    memo.insert(n, res);
+    res }
+
+  /// functional composition: replace input variable v in ctx with the function g.
+  /// Unlike sub(), g can be any bdd, and may share variables with ctx.
+  pub fn compose(&mut self, ctx:NID, v:VID, g:NID)->NID {
+    if let Some(&res) = self.compose_memo.get(&(ctx, v, g)) { return res }
+    let (hi, lo) = (self.when_hi(v, ctx), self.when_lo(v, ctx));
+    let res = self.ite(g, hi, lo);
+    self.compose_memo.insert((ctx, v, g), res);
+    res }
+
+  /// apply an arbitrary function table (of up to 5 inputs) to the given bdds.
+  /// The arguments are matched to the inputs of f in order, so args.len() must equal f.arity().
+  pub fn apply_fun(&mut self, f:NidFun, args:&[NID])->NID {
+    assert_eq!(f.arity() as usize, args.len(), "apply_fun: wrong number of arguments");
+    let mut memo = HashMap::new();
+    self.apply_fun_aux(f, args.to_vec(), &mut memo) }
+
+  fn apply_fun_aux(&mut self, f:NidFun, args:Vec<NID>, memo:&mut HashMap<(NID,Vec<NID>),NID>)->NID {
+    // fold in any constant inputs
+    let (mut f, mut rest, mut i) = (f, vec![], 0);
+    for &arg in args.iter() {
+      if arg.is_const() { f = f.when(i, arg==I) }
+      else { rest.push(arg); i+=1 }}
+    if rest.is_empty() { return if f.tbl()==0 { O } else { I }}
+    let key = (f.to_nid(), rest);
+    if let Some(&res) = memo.get(&key) { return res }
+    // otherwise, branch on the topmost input variable:
+    let v = key.1.iter().map(|x| x.vid()).reduce(topmost).unwrap();
+    let his = key.1.iter().map(|&x| self.when_hi(v, x)).collect();
+    let los = key.1.iter().map(|&x| self.when_lo(v, x)).collect();
+    let hi = self.apply_fun_aux(f, his, memo);
+    let lo = self.apply_fun_aux(f, los, memo);
+    let res = self.ite(NID::from_vid(v), hi, lo);
+    memo.insert(key, res);
+    res }
+
   /// swap input variables x and y within bdd n
   pub fn swap(&mut self, n:NID, x:VID, y:VID)-> NID {
     if x.is_below(&y) { return self.swap(n,y,x) }
@@ -139,23 +499,220 @@ impl BddBase {
     let (xlo, xhi) = (self.when_lo(x,n), self.when_hi(x,n));
     let (xlo_ylo, xlo_yhi) = (self.when_lo(y,xlo), self.when_hi(y,xlo));
     let (xhi_ylo, xhi_yhi) = (self.when_lo(y,xhi), self.when_hi(y,xhi));
-    let lo = self.ite(NID::from_vid(x), xlo_ylo, xhi_ylo);
-    let hi = self.ite(NID::from_vid(y), xlo_yhi, xhi_yhi);
-    self.ite(NID::from_vid(x), lo, hi) }
+    let lo = self.ite(NID::from_vid(y), xhi_ylo, xlo_ylo);
+    let hi = self.ite(NID::from_vid(y), xhi_yhi, xlo_yhi);
+    self.ite(NID::from_vid(x), hi, lo) }
+
+  /// pairs (a, b) of variables from vars such that swapping a and b leaves n unchanged.
+  /// Variables that n doesn't depend on are skipped, since they're trivially symmetric.
+  /// (This just tries every pair, so it's quadratic in vars.len().)
+  pub fn symmetric_vars(&mut self, n:NID, vars:&[VID])->Vec<(VID,VID)> {
+    let support = self.support(n);
+    let vars:Vec<VID> = vars.iter().copied().filter(|v| support.contains(v)).collect();
+    let mut res = vec![];
+    for (i, &a) in vars.iter().enumerate() {
+      for &b in &vars[i+1..] {
+        if self.swap(n, a, b) == n { res.push((a, b)) }}}
+    res }
+
+  /// the set of input variables that n actually depends on.
+  pub fn support(&self, n:NID)->HashSet<VID> {
+    let mut res = HashSet::new();
+    if !n.is_const() { self.walk(n, &mut |_,v,_,_| { res.insert(v); }) }
+    res }
+
+  /// AND together a list of constraints, starting with the ones that depend on
+  /// the fewest variables. This tends to keep the intermediate bdds small.
+  pub fn conjoin_sorted(&mut self, nids:&[NID])->NID {
+    let sizes:Vec<usize> = nids.iter().map(|&n| self.support(n).len()).collect();
+    let mut res = I;
+    for n in apl::at(nids, &apl::gradeup(&sizes)) {
+      res = self.and(res, n);
+      if res == O { break }}
+    res }
+
+  /// conjunction of literals (a product term). Returns I for an empty list,
+  /// and O if some variable appears both plain and negated.
+  pub fn cube(&mut self, lits:&[NID])->NID {
+    match Self::sort_lits(lits) {
+      None => O,
+      Some(lits) => lits.into_iter().fold(I, |res, x| self.and(res, x)) }}
+
+  /// disjunction of literals (a clause). Returns O for an empty list,
+  /// and I if some variable appears both plain and negated.
+  pub fn clause(&mut self, lits:&[NID])->NID {
+    match Self::sort_lits(lits) {
+      None => I,
+      Some(lits) => lits.into_iter().fold(O, |res, x| self.or(res, x)) }}
+
+  /// helper for cube() and clause(): sort and dedup the literals so the variables are added
+  /// from the bottom up. Returns None if a literal and its complement both appear.
+  fn sort_lits(lits:&[NID])->Option<Vec<NID>> {
+    assert!(lits.iter().all(|x| x.is_lit()), "expected a list of literals, got {:?}", lits);
+    let mut res = lits.to_vec();
+    res.sort_by_key(|x| (x.raw(), x.is_inv()));
+    res.dedup();
+    if res.windows(2).any(|w| w[0] == !w[1]) { None } else { Some(res) }}
+
+  /// Rudell's sifting algorithm for variable reordering. Each variable in the
+  /// combined support of nids is moved down and then up through the order (via
+  /// adjacent swaps), and left at whichever position minimized the total node_count.
+  /// Returns the support variables from bottom to top in their new order: that is,
+  /// the ith entry should be relabeled to the ith lowest variable in the support
+  /// (which you can do with permute_inputs()).
+  pub fn sift(&mut self, nids:&[NID])->Vec<VID> { self.sift_with(nids, None) }
+
+  /// same as sift(), but stop moving a variable in a given direction once the
+  /// total size exceeds max_growth times the size before we started moving it.
+  pub fn sift_with(&mut self, nids:&[NID], max_growth:Option<f64>)->Vec<VID> {
+    let mut slots:Vec<VID> = nids.iter().flat_map(|&n| self.support(n)).collect::<HashSet<VID>>()
+      .into_iter().collect();
+    slots.sort_by_key(|v| std::cmp::Reverse(*v)); // bottom first
+    let mut order = slots.clone(); // order[i] = original var now sitting in slots[i]
+    let mut roots = nids.to_vec();
+    let size = |base:&BddBase, roots:&[NID]| roots.iter().map(|&n| base.node_count(n)).sum::<usize>();
+    // sift the variables with the most nodes first.
+    let mut counts:HashMap<VID,usize> = HashMap::new();
+    for &n in nids { if !n.is_const() { self.walk(n, &mut |_,v,_,_| *counts.entry(v).or_default() += 1) }}
+    let mut todo = slots.clone();
+    todo.sort_by_key(|v| std::cmp::Reverse(counts[v]));
+    for v in todo {
+      let mut pos = order.iter().position(|&x| x == v).unwrap();
+      let start = size(self, &roots);
+      let limit = max_growth.map(|g| (start as f64 * g) as usize);
+      let (mut best, mut best_pos) = (start, pos);
+      // swap the var at pos with the one above (up=true) or below it:
+      let mut step = |base:&mut BddBase, roots:&mut Vec<NID>, pos:&mut usize, up:bool| {
+        let other = if up { *pos + 1 } else { *pos - 1 };
+        for r in roots.iter_mut() { *r = base.swap(*r, slots[*pos], slots[other]) }
+        order.swap(*pos, other); *pos = other;
+        size(base, roots) };
+      for up in [false, true] {
+        while if up { pos + 1 < slots.len() } else { pos > 0 } {
+          let now = step(self, &mut roots, &mut pos, up);
+          if now < best { best = now; best_pos = pos }
+          if limit.is_some_and(|l| now > l) { break }}}
+      while pos > best_pos { step(self, &mut roots, &mut pos, false); }
+      while pos < best_pos { step(self, &mut roots, &mut pos, true); }}
+    order }
+
+  /// relabel the inputs of nids so that the variables appear in the given order.
+  /// As with the result of sift(), vids lists the variables from bottom to top, so
+  /// vids[i] is relabeled to xi. If gc is true, the node table is then compacted down
+  /// to just the results (which renumbers every other node in the base).
+  /// Panics if vids isn't a complete order for the nids (see try_reorder).
+  pub fn reorder(&mut self, vids:&[VID], nids:&[NID], gc:bool)->Vec<NID> {
+    self.try_reorder(vids, nids, gc).unwrap_or_else(|e| panic!("reorder: {}", e)) }
+
+  /// same as reorder(), but returns an error instead of panicking when vids isn't a
+  /// permutation of x0..xN (for some N), or leaves out a variable in the support of nids.
+  pub fn try_reorder(&mut self, vids:&[VID], nids:&[NID], gc:bool)->Result<Vec<NID>,ReorderError> {
+    VidOrder::check(vids)?;
+    let mut support:Vec<VID> = nids.iter().flat_map(|&n| self.support(n)).collect();
+    support.sort_by_key(|v| std::cmp::Reverse(*v)); // bottom first, so errors are deterministic
+    if let Some(&v) = support.iter().find(|v| !vids.contains(v)) { return Err(ReorderError::Missing(v)) }
+    let perm:HashMap<VID,VID> = vids.iter().enumerate().map(|(i, &v)| (v, VID::var(i as u32))).collect();
+    let res:Vec<NID> = nids.iter().map(|&n| self.permute_inputs(n, &perm)).collect();
+    if !gc { return Ok(res) }
+    let map = self.gc(&res);
+    Ok(res.iter().map(|n| map[n]).collect()) }
+
+  /// move input v up to the top of n's variable order with adjacent swaps (as sift() does).
+  /// Since the level of a variable in a BddBase is fixed by its vid, "moving" v really
+  /// means relabeling: the topmost variable in the support takes over v's role, and each
+  /// support variable between them shifts down one slot to take over the role of the one
+  /// above it. So the root of the result branches on n's cofactors with respect to v.
+  /// Returns n unchanged if v is not in the support, or is already on top.
+  /// (To undo it, map each slot back to its old role with permute_inputs().)
+  pub fn move_var_to_top(&mut self, n:NID, v:VID)->NID {
+    let support = self.support(n);
+    if !support.contains(&v) { return n }
+    let mut slots:Vec<VID> = support.into_iter().filter(|x| x.is_above(&v)).collect();
+    slots.sort_by_key(|x| std::cmp::Reverse(*x)); // bottom first
+    let (mut res, mut here) = (n, v);
+    for s in slots { res = self.swap(res, here, s); here = s }
+    res }
+
+  /// like dot(), but draws several named roots in one diagram, with any shared
+  /// nodes drawn only once. Each root gets a label with an arrow pointing at its node.
+  pub fn dot_many(&self, roots:&[(String, NID)], wr: &mut dyn std::fmt::Write) {
+    macro_rules! w { ($x:expr $(,$xs:expr)*) => { writeln!(wr, $x $(,$xs)*).unwrap() }}
+    macro_rules! we { ($src:expr, $dst:expr) => {
+      w!("  \"{}\"->\"{}\"{}",$src, $dst,
+        (if $dst.is_inv() & !$dst.is_const() { "[arrowhead=dot]" } else {""})) }}
+    // walk the (non-inverted) roots with a shared seen set, so each node shows up once.
+    let mut nodes = vec![];
+    let mut seen = HashSet::new();
+    for &(_, n) in roots {
+      if !n.is_const() { self.step(n.raw(), &mut |n,v,hi,lo| nodes.push((n,v,hi,lo)), &mut seen, true) }}
+    w!("digraph bdd {{");
+    w!("  bgcolor=\"#3399cc\"; pad=0.225");
+    w!("  node[shape=circle, style=filled, fillcolor=\"#bbbbbb\", fontname=calibri]");
+    w!("  edge[arrowhead=none]");
+    w!("  I[label=⊤, shape=square, fillcolor=white]");
+    w!("  O[label=⊥, shape=square, fontcolor=white, fillcolor=\"#333333\"]");
+    for (i, (name, n)) in roots.iter().enumerate() {
+      let dst = if n.is_const() { *n } else { n.raw() };
+      w!("  root{}[label=\"{}\", shape=plaintext, fillcolor=none]", i, dot_escape(name));
+      w!("  root{}->\"{}\"[arrowhead={}]", i, dst, if n.is_inv() && !n.is_const() { "dot" } else { "normal" }); }
+    for &(n,v,_,_) in &nodes { w!("  \"{}\"[label=\"{}\"];", n, v); }
+    w!("edge[style=solid];");
+    for &(n,_,hi,_) in &nodes { we!(n, hi); }
+    w!("edge[style=dashed];");
+    for &(n,_,_,lo) in &nodes { we!(n, lo); }
+    w!("}}"); }
+
+  /// render node `n` (and its descendents) in graphviz *.dot format, with the given style.
+  pub fn dot_styled(&self, n:NID, style:&DotStyle, wr: &mut dyn std::fmt::Write) {
+    macro_rules! w { ($x:expr $(,$xs:expr)*) => { writeln!(wr, $x $(,$xs)*).unwrap() }}
+    macro_rules! we { ($src:expr, $dst:expr) => {
+      w!("  \"{}\"->\"{}\"{}",$src, $dst,
+        (if $dst.is_inv() & !$dst.is_const() { "[arrowhead=dot]" } else {""})) }}
+    w!("digraph bdd {{");
+    w!("  bgcolor=\"{}\"; pad=0.225", style.bgcolor);
+    w!("  node[shape=circle, style=filled, fillcolor=\"{}\", fontname=\"{}\"]", style.node_fill, style.font);
+    w!("  edge[arrowhead=none]");
+    w!("  subgraph head {{ h1[shape=plaintext, fillcolor=none, label=\"BDD\"] }}");
+    w!("  I[label=⊤, shape={}, fillcolor=white]", style.terminal_shape);
+    w!("  O[label=⊥, shape={}, fontcolor=white, fillcolor=\"#333333\"]", style.terminal_shape);
+    if n.is_inv() {
+      w!("hook[label=\"\",shape=plain,style=invis]; hook->{}:n[arrowhead=dot,penwidth=0,minlen=0,constraint=false]", n); }
+    self.walk(n, &mut |n,_,_,_| w!("  \"{}\"[label=\"{}\"];", n, n.vid()));
+    // line up the nodes that branch on the same variable, and the terminals at the bottom.
+    let mut levels:HashMap<VID,Vec<NID>> = HashMap::new();
+    self.walk(n, &mut |n,v,_,_| levels.entry(v).or_default().push(n));
+    let mut vids:Vec<VID> = levels.keys().copied().collect(); vids.sort(); // (topmost first)
+    for v in &vids {
+      write!(wr, "  {{rank=same").unwrap();
+      for n in &levels[v] { write!(wr, " \"{}\"", n).unwrap() }
+      w!("}}") }
+    w!("  {{rank=same I O}}");
+    w!("edge[style=solid];");
+    self.walk(n, &mut |n,_,t,_| we!(n, t));
+    w!("edge[style=dashed];");
+    self.walk(n, &mut |n,_,_,e| we!(n, e));
+    w!("}}"); }
 
   pub fn node_count(&self, n:NID)->usize {
     let mut c = 0; self.walk(n, &mut |_,_,_,_| c+=1); c }
+
+  /// like node_count, but counts the distinct nodes reachable from any of the roots,
+  /// so that structure shared between them is only counted once.
+  pub fn shared_node_count(&self, roots:&[NID])->usize {
+    let mut c = 0; let mut seen = HashSet::new();
+    // (n and !n are the same node, so walk from the raw nid)
+    for &n in roots { if !n.is_const() { self.step(n.raw(), &mut |_,_,_,_| c+=1, &mut seen, true) }}
+    c }
 
   /// helper for truth table builder
-  fn tt_aux(&mut self, res:&mut Vec<u8>, n:NID, i:usize, level:u32) {
-    if level == 0 { match n {
+  fn tt_aux(&mut self, res:&mut Vec<u8>, n:NID, i:usize, vars:&[VID]) {
+    if let Some((&v, rest)) = vars.split_last() {
+      let lo = self.when_lo(v,n); self.tt_aux(res, lo, i*2, rest);
+      let hi = self.when_hi(v,n); self.tt_aux(res, hi, i*2+1, rest); }
+    else { match n {
       O => {} // res[i] = 0; but this is already the case.
       I => { res[i] = 1; }
-      x => panic!("expected a leaf nid, got {}", x) }}
-    else {
-      let v = VID::var(level-1);
-      let lo = self.when_lo(v,n); self.tt_aux(res, lo, i*2, level-1);
-      let hi = self.when_hi(v,n); self.tt_aux(res, hi, i*2+1, level-1); }}
+      x => panic!("expected a leaf nid, got {}", x) }}}
 
   /// Truth table. Could have been `Vec<bool>` but this is mostly for testing
   /// and the literals are much smaller when you type `1` and `0` instead of
@@ -165,16 +722,78 @@ impl BddBase {
     if !n0.vid().is_var() { todo!("tt only works for actual variables. got {:?}", n0); }
     if num_vars > 16 { panic!("refusing to generate a truth table of 2^{} bytes", num_vars) }
     if num_vars == 0 { panic!("num_vars should be > 0")}
-    let mut res = vec![0;(1 << num_vars) as usize];
-    self.tt_aux(&mut res, n0, 0, num_vars);
+    let vars:Vec<VID> = (0..num_vars).map(VID::var).collect();
+    self.tt_for_vars(n0, &vars) }
+
+  /// Truth table over exactly the given variables. vars[0] is the least significant
+  /// bit of the index, so `tt(n, k)` is the same as `tt_for_vars(n, &[x0, x1, .. x(k-1)])`.
+  /// Panics if n depends on any variable that isn't in the list.
+  pub fn tt_for_vars(&mut self, n:NID, vars:&[VID])->Vec<u8> {
+    if vars.len() > 16 { panic!("refusing to generate a truth table of 2^{} bytes", vars.len()) }
+    let mut res = vec![0;1 << vars.len()];
+    self.tt_aux(&mut res, n, 0, vars);
     res }
 
+  /// Build a bdd from a truth table (the inverse of `tt_for_vars`).
+  /// bits.len() must be 2^vars.len(), and vars[0] is the least significant bit of the index.
+  pub fn from_tt(&mut self, bits:&[u8], vars:&[VID])->NID {
+    assert_eq!(bits.len(), 1 << vars.len(), "from_tt: truth table should have 2^{} entries", vars.len());
+    if let Some((&v, rest)) = vars.split_last() {
+      // the top var selects between the two halves of the table:
+      let (lo_bits, hi_bits) = bits.split_at(bits.len()/2);
+      let lo = self.from_tt(lo_bits, rest);
+      let hi = self.from_tt(hi_bits, rest);
+      self.ite(NID::from_vid(v), hi, lo) }
+    else if bits[0] == 0 { O } else { I }}
+
+  /// write the node table and tags to disk.
+  pub fn save(&self, path:&str)->io::Result<()> {
+    let data = BddSaveData{ tags: self.tags.clone(), hilos: self.swarm.hilos().to_vec() };
+    write_versioned(path, BDD_FORMAT_VERSION, &data) }
+
+  /// load a base previously written by `save`.
+  pub fn load(path:&str)->io::Result<BddBase> {
+    let data:BddSaveData = read_versioned(path, BDD_FORMAT_VERSION, "bdd")?;
+    let mut res = BddBase::new();
+    for hilo in data.hilos { res.swarm.hilos().push(hilo); }
+    res.tags = data.tags;
+    Ok(res) }
+
+  /// build a bdd from a DIMACS CNF file. Variable k in the file maps to `VID::var(k-1)`.
+  /// Returns the new base and the root of the conjunction of all clauses.
+  pub fn from_dimacs(path:&str)->io::Result<(BddBase, NID)> {
+    let text = std::fs::read_to_string(path)?;
+    let bad = |msg:String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg));
+    let mut base = BddBase::new();
+    let (mut res, mut clause) = (I, vec![]);
+    for line in text.lines() {
+      let line = line.trim();
+      if line.starts_with('c') || line.is_empty() { continue }
+      if line.starts_with('p') {
+        if line.split_whitespace().nth(1) != Some("cnf") {
+          return Err(bad(format!("expected 'p cnf' header, got '{}'", line))) }
+        continue }
+      if line.starts_with('%') { break } // some benchmark files end with "%\n0"
+      for tok in line.split_whitespace() {
+        let lit:i64 = tok.parse().map_err(|_| bad(format!("bad literal '{}'", tok)))?;
+        if lit == 0 { let c = base.clause(&clause); res = base.and(res, c); clause.clear(); }
+        else {
+          let x = NID::from_vid(VID::var(lit.unsigned_abs() as u32 - 1));
+          clause.push(if lit < 0 { !x } else { x }); }}}
+    if !clause.is_empty() { let c = base.clause(&clause); res = base.and(res, c) } // tolerate a missing final 0
+    Ok((base, res)) }
+
   pub fn get_stats(&mut self)->(u64, u64) {
     self.swarm.get_stats();
     let tests = wip::COUNT_CACHE_TESTS.with(|c| *c.borrow());
     let hits = wip::COUNT_CACHE_HITS.with(|c| *c.borrow());
     (tests, hits)}
 
+  /// cache statistics (as in get_stats()), along with the current table sizes.
+  pub fn stats(&mut self)->CacheStats {
+    let (tests, hits) = self.get_stats();
+    CacheStats{ tests, hits, node_count: self.len(), memo_entries: self.swarm.cache_len() }}
+
 }
 
 impl Default for BddBase { fn default() -> Self { Self::new() }}
@@ -182,7 +801,7 @@ impl Default for BddBase { fn default() -> Self { Self::new() }}
 
 impl Base for BddBase {
 
-  fn new()->BddBase { BddBase{swarm: BddSwarm::new(), tags:HashMap::new()}}
+  fn new()->BddBase { BddBase{swarm: BddSwarm::new(), tags:HashMap::new(), op_log:None, compose_memo:HashMap::new()}}
 
   /// nid of y when x is high
   fn when_hi(&mut self, x:VID, y:NID)->NID {
@@ -206,6 +825,9 @@ impl Base for BddBase {
         let (th,el) = (self.when_lo(x,yt), self.when_lo(x,ye));
         self.ite(NID::from_vid(yv), th, el) }}}
 
+  fn node_count(&self, n:NID)->usize { BddBase::node_count(self, n) }
+  fn support(&self, n:NID)->HashSet<VID> { BddBase::support(self, n) }
+
   // TODO: these should be moved into seperate struct
   fn def(&mut self, _s:String, _i:VID)->NID { todo!("BddBase::def()") }
   fn tag(&mut self, n:NID, s:String)->NID { self.tags.insert(s, n); n }
@@ -227,26 +849,7 @@ impl Base for BddBase {
     else { ctx }}
 
   // generate dot file (graphviz)
-  fn dot(&self, n:NID, wr: &mut dyn std::fmt::Write) {
-    macro_rules! w { ($x:expr $(,$xs:expr)*) => { writeln!(wr, $x $(,$xs)*).unwrap() }}
-    macro_rules! we { ($src:expr, $dst:expr) => {
-      w!("  \"{}\"->\"{}\"{}",$src, $dst,
-        (if $dst.is_inv() & !$dst.is_const() { "[arrowhead=dot]" } else {""})) }}
-    w!("digraph bdd {{");
-    w!("  bgcolor=\"#3399cc\"; pad=0.225");
-    w!("  node[shape=circle, style=filled, fillcolor=\"#bbbbbb\", fontname=calibri]");
-    w!("  edge[arrowhead=none]");
-    w!("  subgraph head {{ h1[shape=plaintext, fillcolor=none, label=\"BDD\"] }}");
-    w!("  I[label=⊤, shape=square, fillcolor=white]");
-    w!("  O[label=⊥, shape=square, fontcolor=white, fillcolor=\"#333333\"]");
-    if n.is_inv() {
-      w!("hook[label=\"\",shape=plain,style=invis]; hook->{}:n[arrowhead=dot,penwidth=0,minlen=0,constraint=false]", n); }
-    self.walk(n, &mut |n,_,_,_| w!("  \"{}\"[label=\"{}\"];", n, n.vid()));
-    w!("edge[style=solid];");
-    self.walk(n, &mut |n,_,t,_| we!(n, t));
-    w!("edge[style=dashed];");
-    self.walk(n, &mut |n,_,_,e| we!(n, e));
-    w!("}}"); }
+  fn dot(&self, n:NID, wr: &mut dyn std::fmt::Write) { self.dot_styled(n, &DotStyle::default(), wr) }
 
   fn init_stats(&mut self) {
     wip::COUNT_CACHE_TESTS.with(|c| c.replace(0));
@@ -1,6 +1,6 @@
 //! Solution iterator for BddBase
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use crate::vhl::{HiLo, HiLoBase, Walkable};
 use crate::{vid::VID, nid::{NID,I,O}, bdd::BddBase, reg::Reg};
 use crate::cur::{Cursor, CursorPlan};
@@ -13,15 +13,22 @@ impl HiLoBase for BddBase {
     Some(HiLo{ hi, lo }) }}
 
 impl Walkable for BddBase {
-  /// internal helper: one step in the walk.
+  /// internal helper: the walk itself.
+  /// This uses an explicit stack rather than recursion, so that very deep bdds
+  /// don't overflow the call stack. The visiting order is the same as a recursive
+  /// depth-first walk: lo branch before hi branch, with each node visited either
+  /// before (topdown) or after (bottom up) both of its children.
   fn step<F>(&self, n:NID, f:&mut F, seen:&mut HashSet<NID>, topdown:bool)
   where F: FnMut(NID,VID,NID,NID) {
-    if !seen.contains(&n) {
-      seen.insert(n); let (hi,lo) = self.tup(n);
-      if topdown { f(n, n.vid(), hi,lo ) }
-      if !lo.is_const() { self.step(lo, f, seen, topdown) }
-      if !hi.is_const() { self.step(hi, f, seen, topdown) }
-      if !topdown { f(n, n.vid(), hi, lo) }}}}
+    // the flag says whether we've already pushed the node's children.
+    let mut stack = vec![(n, false)];
+    while let Some((n, expanded)) = stack.pop() {
+      let (hi, lo) = self.tup(n);
+      if expanded { f(n, n.vid(), hi, lo); continue }
+      if !seen.insert(n) { continue }
+      if topdown { f(n, n.vid(), hi, lo) } else { stack.push((n, true)) }
+      if !hi.is_const() { stack.push((hi, false)) }
+      if !lo.is_const() { stack.push((lo, false)) }}}}
 
 pub struct BDDSolIterator<'a> {
   bdd: &'a BddBase,
@@ -48,16 +55,56 @@ impl Iterator for BDDSolIterator<'_> {
 
 /// Solution iterators.
 impl BddBase {
-  pub fn solutions(&mut self, n:NID)->BDDSolIterator {
+  pub fn solutions(&mut self, n:NID)->BDDSolIterator<'_> {
     let nvars = if n.is_const() { 1 } else if n.vid().is_var() { n.vid().var_ix() }
     else if n.vid().is_vir() {
       panic!("It probably doesn't make sense to call solutions(n) when n.vid().is_vir(), but you can try solutions_pad() if you think it makes sense.") }
     else { panic!("Don't know how to find solutions({:?}). Maybe try solutions_pad()...?", n) };
     self.solutions_pad(n, nvars)}
 
-  pub fn solutions_pad(&self, n:NID, nvars:usize)->BDDSolIterator {
+  pub fn solutions_pad(&self, n:NID, nvars:usize)->BDDSolIterator<'_> {
     BDDSolIterator::from_bdd(self, n, nvars)}
 
+  /// lazily enumerate the solutions over the first nvars input variables, in increasing
+  /// order of `Reg::as_usize` (x0 is the least significant bit). Unlike solution_set(),
+  /// this never holds more than one solution at a time.
+  pub fn satisfy_all(&self, n:NID, nvars:usize)->impl Iterator<Item=Reg> + '_ {
+    self.solutions_pad(n, nvars) }
+
+  /// count the solutions over the first nvars input variables, without enumerating them.
+  pub fn solution_count(&self, n:NID, nvars:usize)->u64 {
+    assert!(nvars < 64, "solution_count only works for < 64 variables");
+    let level = |x:NID|->usize { if x.is_const() { 0 } else { x.vid().var_ix()+1 }};
+    let mut memo:HashMap<NID,u64> = HashMap::new();
+    let get = |y:NID, memo:&HashMap<NID,u64>|->u64 {
+      if y.is_const() { if y == I { 1 } else { 0 }} else { memo[&y] }};
+    // memo[x] = number of solutions over the variables at or below x
+    if !n.is_const() { self.walk_up(n, &mut |x, v, hi, lo| {
+      let i = v.var_ix();
+      let c = (get(hi, &memo) << (i-level(hi))) + (get(lo, &memo) << (i-level(lo)));
+      memo.insert(x, c); })}
+    assert!(level(n) <= nvars, "{} depends on more than {} variables", n, nvars);
+    get(n, &memo) << (nvars - level(n)) }
+
+  /// weighted model count over the first nvars input variables: each solution
+  /// contributes the product of weights[v].0 for every true v and weights[v].1 for
+  /// every false v. Variables missing from weights count as (1.0, 1.0), so with an
+  /// empty map this is just solution_count (as a float).
+  pub fn weighted_count(&self, n:NID, weights:&HashMap<VID,(f64,f64)>, nvars:usize)->f64 {
+    let level = |x:NID|->usize { if x.is_const() { 0 } else { x.vid().var_ix()+1 }};
+    let w = |i:usize|->(f64,f64) { *weights.get(&VID::var(i as u32)).unwrap_or(&(1.0, 1.0)) };
+    // total weight of the variables in levels lo..hi (which can take either value)
+    let skip = |lo:usize, hi:usize|->f64 { (lo..hi).map(|i| { let (t, f) = w(i); t + f }).product() };
+    let mut memo:HashMap<NID,f64> = HashMap::new();
+    let get = |y:NID, memo:&HashMap<NID,f64>|->f64 {
+      if y.is_const() { if y == I { 1.0 } else { 0.0 }} else { memo[&y] }};
+    if !n.is_const() { self.walk_up(n, &mut |x, v, hi, lo| {
+      let i = v.var_ix(); let (wt, wf) = w(i);
+      let c = wt * get(hi, &memo) * skip(level(hi), i) + wf * get(lo, &memo) * skip(level(lo), i);
+      memo.insert(x, c); })}
+    assert!(level(n) <= nvars, "{} depends on more than {} variables", n, nvars);
+    get(n, &memo) * skip(level(n), nvars) }
+
   pub fn first_solution(&self, n:NID, nvars:usize)->Option<Cursor> {
     if n== O || nvars == 0 { None }
     else {
@@ -67,6 +114,30 @@ impl BddBase {
       debug_assert!(self.in_solution(&cur), "{:?}", cur.scope);
       Some(cur) }}
 
+  /// count the distinct paths from n to I. Unlike solution_count, this doesn't
+  /// account for variables that get skipped along a path.
+  pub fn path_count(&self, n:NID)->u64 {
+    let mut memo:HashMap<NID,u64> = HashMap::new();
+    let get = |y:NID, memo:&HashMap<NID,u64>|->u64 {
+      if y.is_const() { if y == I { 1 } else { 0 }} else { memo[&y] }};
+    if !n.is_const() { self.walk_up(n, &mut |x, _, hi, lo| {
+      let c = get(hi, &memo) + get(lo, &memo);
+      memo.insert(x, c); })}
+    get(n, &memo) }
+
+  /// return a single solution (a witness for satisfiability) without setting up
+  /// an iterator. This walks down from n, preferring the lo branch whenever it
+  /// isn't O. Variables that get skipped along the way are left as 0.
+  pub fn first_solution_reg(&self, n:NID, nvars:usize)->Option<Reg> {
+    if n == O { return None }
+    let mut reg = Reg::new(nvars);
+    let mut x = n;
+    while !x.is_const() {
+      let (hi, lo) = self.tup(x);
+      if lo != O { x = lo } else { reg.var_put(x.vid(), true); x = hi }}
+    debug_assert_eq!(x, I);
+    Some(reg) }
+
   pub fn next_solution(&self, cur:Cursor)->Option<Cursor> {
     self.log(&cur, "advance>"); self.log_indent(1);
     let res = self.advance0(cur); self.log_indent(-1);
@@ -84,43 +155,6 @@ impl BddBase {
     //   print!(" {:?}{}", _c.scope, if self.in_solution(_c) { '.' } else { ' ' });
     //   println!(" {:50} {:?}", _msg, _c.nstack);}}
 
-
-  /// walk depth-first from lo to hi until we arrive at the next solution
-  fn find_next_leaf(&self, cur:&mut Cursor)->Option<NID> {
-    self.log(cur, "find_next_leaf"); self.log_indent(1);
-    let res = self.find_next_leaf0(cur);
-    self.log(cur, format!("^ next leaf: {:?}", res.clone()).as_str());
-    self.log_indent(-1); res }
-
-  fn find_next_leaf0(&self, cur:&mut Cursor)->Option<NID> {
-    // we always start at a leaf and move up, with the one exception of root=I
-    assert!(cur.node.is_const(), "find_next_leaf should always start by looking at a leaf");
-    if cur.nstack.is_empty() { assert!(cur.node == I); return None }
-
-    // now we are definitely at a leaf node with a branch above us.
-    cur.step_up();
-
-    let tv = cur.node.vid(); // branching var for current twig node
-    let mut rippled = false;
-    // if we've already walked the hi branch...
-    if cur.scope.var_get(tv) {
-      cur.go_next_lo_var();
-      // if we've cleared the stack and already explored the hi branch...
-      { let iv = cur.node.vid();
-        if cur.nstack.is_empty() && cur.scope.var_get(iv) {
-          // ... then first check if there are any variables above us on which
-          // the node doesn't actually depend. ifso: ripple add. else: done.
-          let top = cur.nvars-1;
-          if let Some(x) = cur.scope.ripple(iv.var_ix(), top) {
-            rippled = true;
-            self.log(cur, format!("rippled top to {}. restarting.", x).as_str()); }
-          else { self.log(cur, "no next leaf!"); return None }}} }
-
-    if rippled { cur.clear_trailing_bits() }
-    else if cur.var_get() { self.log(cur, "done with node."); return None }
-    else { cur.put_step(self, true); }
-    cur.descend(self);
-    Some(cur.node) }
 
   /// walk depth-first from lo to hi until we arrive at the next solution
   fn advance0(&self, mut cur:Cursor)->Option<Cursor> {
@@ -138,5 +172,6 @@ impl BddBase {
       else { // overflow. we've counted all the way to 2^nvars-1, and we're done.
         self.log(&cur, "$ found all solutions!"); return None }}
     // If still here, we are looking at a leaf that isn't a solution (out=0 in truth table)
-    while !self.in_solution(&cur) { self.find_next_leaf(&mut cur)?; }
+    while !self.in_solution(&cur) {
+      if !cur.skip_dead_leaf(self) { self.log(&cur, "no next leaf!"); return None }}
     Some(cur) }}
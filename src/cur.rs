@@ -68,6 +68,41 @@ impl Cursor {
       let choice = !base.includes_lo(hl.lo);
       self.put_step(base, choice) }}
 
+  /// move past a leaf that isn't a solution: set the scope to the smallest assignment
+  /// above the current one that changes at least one variable on the current path
+  /// (anything else would just lead back to this same leaf), then descend to the new leaf.
+  /// Returns false if there's no such assignment.
+  pub fn skip_dead_leaf(&mut self, base: &dyn CursorPlan)->bool {
+    assert!(self.node.is_const(), "skip_dead_leaf should always start by looking at a leaf");
+    let path:Vec<usize> = self.nstack.iter().map(|n| n.vid().var_ix()).collect();
+    // the lowest 0 bit we can set: either a branch on the path, or a skipped variable
+    // with a 1 on the path below it (which gets cleared when we set the higher bit).
+    let Some(d) = (0..self.nvars).find(|&d| !self.scope.get(d)
+      && (path.contains(&d) || path.iter().any(|&p| p < d && self.scope.get(p)))) else { return false };
+    self.scope.put(d, true);
+    for i in 0..d { self.scope.put(i, false) }
+    let vd = VID::var(d as u32);
+    while !self.nstack.is_empty() && !vd.is_below(&self.nstack[self.nstack.len()-1].vid()) {
+      self.pop_node(); }
+    self.put_step(base, self.var_get());
+    self.descend(base);
+    true }
+
+  /// position the cursor at the first solution whose scope is >= target (comparing them
+  /// as numbers, with x0 as the least significant bit). If target itself is a solution,
+  /// that's where we land. Otherwise we advance to the next one, and return false if
+  /// there isn't one. This lets you split up the enumeration of a big solution space
+  /// into separate ranges (and resume from any of them).
+  pub fn seek(&mut self, base: &dyn CursorPlan, target:&Reg)->bool {
+    assert_eq!(target.len(), self.nvars, "target should have one bit per variable");
+    while !self.nstack.is_empty() { self.pop_node() }
+    self.scope = target.clone();
+    // follow the target's bits down to a leaf:
+    while !self.node.is_const() { self.put_step(base, self.var_get()) }
+    while !base.includes_leaf(self.node) {
+      if !self.skip_dead_leaf(base) { return false }}
+    true }
+
   pub fn var_get(&self)->bool {
     self.scope.var_get(self.node.vid()) }
 
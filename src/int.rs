@@ -3,6 +3,7 @@ extern crate std;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::cmp::min;
+use std::collections::HashMap;
 use crate::ast::ASTBase;
 use crate::base::Base;
 use crate::{nid, nid::NID};
@@ -101,17 +102,62 @@ pub trait BInt : Sized {
     for i in 0..Self::n() { res.set(i, self.get((i+y) % Self::n())) }
     res}
 
+  /// logical shift left by a constant amount, filling with `O`.
+  /// (shifting by the width or more gives zero.)
+  fn shl(&self, k:usize) -> Self {
+    let mut res = Self::zero();
+    for i in (k as u32)..Self::n() { res.set(i, self.get(i - k as u32)) }
+    res}
+
+  /// logical shift right by a constant amount, filling with `O`.
+  /// (shifting by the width or more gives zero.)
+  fn shr(&self, k:usize) -> Self {
+    let mut res = Self::zero();
+    for i in (k as u32)..Self::n() { res.set(i - k as u32, self.get(i)) }
+    res}
+
   fn def(s:&str, start:u32)->Self;
 
-  // TODO: this doesn't actually wrap! (should it??)
-  fn wrapping_add(&self, y:Self) -> Self {
+  /// an integer with constant bits. (same as `new`, but takes a u64.)
+  fn constant(value:u64)->Self { Self::new(value as usize) }
+
+  /// evaluate every bit under the given assignment of input variables, and pack the
+  /// results into an integer (bit 0 is the least significant). Returns None if any
+  /// bit still depends on a variable that isn't assigned.
+  fn eval(&self, env:&HashMap<VID,NID>)->Option<u64> {
+    let kvs:HashMap<NID,NID> = env.iter().map(|(&v, &n)| (NID::from_vid(v), n)).collect();
+    let nids:Vec<NID> = (0..Self::n()).map(|i| self.get(i).n).collect();
+    let vals = self.o().base.borrow_mut().raw_ast_mut().eval_all(&nids, &kvs);
+    let mut res = 0;
+    for (i, v) in vals.into_iter().enumerate() {
+      if v == nid::I { res |= 1 << i } else if v != nid::O { return None }}
+    Some(res) }
+
+  /// ripple-carry addition. The final carry is dropped, so the result
+  /// wraps around at the width boundary: `(self + other) mod 2^n`.
+  fn plus(&self, other:&Self) -> Self {
     let mut res = Self::zero(); let mut carry = self.o();
     for i in 0..Self::n() {
-      let (a,b,c) = (self.get(i), y.get(i), carry);
+      let (a,b,c) = (self.get(i), other.get(i), carry);
       res.set(i, a.clone() ^ b.clone() ^ c.clone());
       carry = bitmaj(a, b, c);}
     res}
 
+  /// two's-complement negation (`!self + 1`). Like `plus`, this wraps at
+  /// the width boundary, so the most negative value is its own negation.
+  fn negate(&self) -> Self {
+    let mut inv = Self::zero();
+    for i in 0..Self::n() { inv.set(i, !self.get(i)) }
+    let mut one = Self::zero(); one.set(0, self.i());
+    inv.plus(&one) }
+
+  /// two's-complement subtraction: `self.plus(&other.negate())`,
+  /// so it wraps around at the width boundary, just like `plus`.
+  fn minus(&self, other:&Self) -> Self { self.plus(&other.negate()) }
+
+  /// same as `plus`, but takes ownership of `y`.
+  fn wrapping_add(&self, y:Self) -> Self { self.plus(&y) }
+
   fn from<B:BInt>(other:&B) -> Self {
     let mut res = Self::zero();
     for i in 0..min(Self::n(),B::n()) { res.set(i, other.get(i).clone()) }
@@ -119,6 +165,10 @@ pub trait BInt : Sized {
 
   fn eq(&self, other:&Self)-> BaseBit;
   fn lt(&self, other:&Self)-> BaseBit;
+  fn le(&self, other:&Self)-> BaseBit { self.lt(other) | BInt::eq(self, other) }
+  fn gt(&self, other:&Self)-> BaseBit { !self.le(other) }
+  fn ge(&self, other:&Self)-> BaseBit { !self.lt(other) }
+  fn ne(&self, other:&Self)-> BaseBit { !BInt::eq(self, other) }
 
   fn times<B:BInt>(&self, y0:&Self) -> B {
     let mut sum = B::zero();
@@ -227,10 +277,7 @@ macro_rules! xint_type {
 
     impl std::ops::Shr<u32> for $T {
       type Output = Self;
-      fn shr(self, y:u32) -> Self {
-        let mut res = Self::zero();
-        for i in 0..($n-y) { res.bits[i as usize] = self.bits[(i+y) as usize].clone() }
-        res }}
+      fn shr(self, y:u32) -> Self { BInt::shr(&self, y as usize) }}
 
     impl std::ops::Not for $T {
       type Output = Self;
@@ -259,6 +306,36 @@ xint_type!(64, X64); pub fn x64(u:usize)->X64 { X64::new(u) }
 #[test] fn test_add() {
   assert_eq!((x32(2).wrapping_add(x32(3))).u(), 5) }
 
+/// evaluate the bits of an integer under the given variable assignment.
+#[cfg(test)] fn eval_bits<B:BInt>(x:&B, kvs:&HashMap<NID,NID>)->usize {
+  let nids:Vec<NID> = (0..B::n()).map(|i| x.get(i).n).collect();
+  let vals = gbase_ref().borrow_mut().raw_ast_mut().eval_all(&nids, kvs);
+  vals.iter().enumerate().map(|(i,&v)| if v == nid::I { 1<<i } else { 0 }).sum() }
+
+#[test] fn test_plus() {
+  assert_eq!(x8(100).plus(&x8(27)).u(), 127);
+  assert_eq!(x8(200).plus(&x8(100)).u(), 44, "plus should wrap at 256");
+  // symbolic version:
+  let x = X8::def("x", 0);
+  let y = x.plus(&x8(100));
+  let kvs = (0..8).map(|i| (NID::var(i), if 200 & (1<<i) != 0 { nid::I } else { nid::O })).collect();
+  assert_eq!(eval_bits(&y, &kvs), 44); }
+
+#[test] fn test_minus() {
+  assert_eq!(x8(100).minus(&x8(27)).u(), 73);
+  assert_eq!(x8(27).minus(&x8(100)).u(), 256-73, "minus should wrap at 0");
+  assert_eq!(x8(1).negate().u(), 255);
+  assert_eq!(x8(128).negate().u(), 128);
+  assert_eq!(x8(0).negate().u(), 0); }
+
+#[test] fn test_minus_self_is_zero() {
+  use crate::{bdd::BddBase, solve::solve};
+  let x = X4::def("x", 0);
+  let z = BInt::eq(&x.minus(&x), &X4::zero());
+  let mut bdd = BddBase::new();
+  let res = solve(&mut bdd, gbase_ref().borrow().raw_ast(), z.n);
+  assert_eq!(res.n, nid::I, "x - x == 0 should be a tautology"); }
+
 #[test] fn test_mul32() {
   assert_eq!((x32(2).times::<X32>(&x32(3))).u(),  6);
   assert_eq!((x32(3).times::<X32>(&x32(5))).u(), 15) }
@@ -282,3 +359,34 @@ xint_type!(64, X64); pub fn x64(u:usize)->X64 { X64::new(u) }
   assert_eq!(BInt::eq(&x32(10), &x32(10)), gbase_i());
   assert_eq!(BInt::eq(&x32(11), &x32(10)), gbase_o());
   assert_eq!(BInt::eq(&x32(10), &x32(11)), gbase_o()); }
+
+#[test] fn test_cmp() {
+  let b = |x:bool| if x { gbase_i() } else { gbase_o() };
+  for (x, y) in [(3u8, 5u8), (5, 3), (7, 7), (0, 15)] {
+    let (bx, by) = (x4(x as usize), x4(y as usize));
+    assert_eq!(bx.lt(&by), b(x < y), "{x} < {y}");
+    assert_eq!(bx.le(&by), b(x <= y), "{x} <= {y}");
+    assert_eq!(bx.gt(&by), b(x > y), "{x} > {y}");
+    assert_eq!(bx.ge(&by), b(x >= y), "{x} >= {y}");
+    assert_eq!(BInt::eq(&bx, &by), b(x == y), "{x} == {y}");
+    assert_eq!(BInt::ne(&bx, &by), b(x != y), "{x} != {y}"); }}
+
+#[test] fn test_shifts() {
+  for k in [0, 1, 77, 128, 200, 255] {
+    assert_eq!(x8(k).shl(1).u(), (k * 2) % 256, "{k} << 1"); }
+  assert_eq!(x8(0b1011).shl(3).u(), 0b1011000);
+  assert_eq!(x8(0b1011000).shr(3).u(), 0b1011);
+  assert_eq!((x8(0b1011000) >> 3).u(), 0b1011);
+  assert_eq!(x8(255).shl(8).u(), 0);
+  assert_eq!(x8(255).shr(9).u(), 0); }
+
+#[test] fn test_constant_eval() {
+  let empty = HashMap::new();
+  assert_eq!(X8::constant(42).eval(&empty), Some(42));
+  assert_eq!(X64::constant(u64::MAX).eval(&empty), Some(u64::MAX));
+  let x = X4::def("eval", 0);
+  let y = x.plus(&X4::constant(3));
+  assert_eq!(y.eval(&empty), None, "the bits of x are still symbolic");
+  let env:HashMap<VID,NID> = (0..4).map(|i| (VID::var(i), if 5 & (1<<i) == 0 { nid::O } else { nid::I })).collect();
+  assert_eq!(x.eval(&env), Some(5));
+  assert_eq!(y.eval(&env), Some(8)); }
@@ -1,8 +1,9 @@
 //! binary io for hashmap<String,NID> and typed vectors
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
 use std::io::prelude::*;
 use std::{collections::HashMap, hash::BuildHasher};
+use serde::{Serialize, de::DeserializeOwned};
 
 
 // these functions treat typed slices as raw bytes, making them easier to read/write
@@ -18,6 +19,29 @@ unsafe fn u8s_to_slice<T: Sized>(p: &[u8]) -> &[T] {
     p.len() / ::std::mem::size_of::<T>()) }
 
 
+/// report a (de)serialization failure as an io::Error, so save/load functions can use `?`.
+fn bincode_err(e:bincode::Error)->std::io::Error {
+  std::io::Error::new(std::io::ErrorKind::InvalidData, e) }
+
+/// serialize data to a file, preceded by a format version number. The `save` methods
+/// on the various bases use this, each with its own version constant, which should be
+/// bumped whenever the serialized layout changes.
+pub(crate) fn write_versioned<T:Serialize>(path:&str, version:u32, data:&T)->std::io::Result<()> {
+  let mut f = BufWriter::new(File::create(path)?);
+  bincode::serialize_into(&mut f, &version).map_err(bincode_err)?;
+  bincode::serialize_into(&mut f, data).map_err(bincode_err) }
+
+/// read back data written by `write_versioned`, failing if the file has a different
+/// version number. (`what` names the format in the error message, like "bdd".)
+pub(crate) fn read_versioned<T:DeserializeOwned>(path:&str, version:u32, what:&str)->std::io::Result<T> {
+  let mut f = BufReader::new(File::open(path)?);
+  let found:u32 = bincode::deserialize_from(&mut f).map_err(bincode_err)?;
+  if found != version {
+    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+      "{} has {} format version {}, but this build of bex expects version {}",
+      path, what, found, version))) }
+  bincode::deserialize_from(&mut f).map_err(bincode_err) }
+
 /// write the vector, as bytes, to a file at the specified path.
 pub fn put<T:Sized>(path:&str, v:&[T]) -> ::std::io::Result<()> {
   let mut f = File::create(path)?;
@@ -9,6 +9,8 @@ extern crate dashmap;
 extern crate boxcar;
 extern crate fxhash;
 extern crate concurrent_queue;
+extern crate serde;
+extern crate bincode;
 
 pub mod base;   pub use crate::base::{Base, GraphViz};
 pub mod vid;
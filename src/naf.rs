@@ -4,12 +4,13 @@
  * version allows deferred evaluation.
  * (Note: this module is experimental and far from stable.)
  */
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use dashmap::DashMap;
 use crate::ops::Ops;
 use crate::{ops, simp, vhl::Vhl};
 use crate::{NID, I, O, vid::VID};
 use crate::{ast::RawASTBase, vid::{topmost, VidOrdering}};
+use crate::{base::Base, bdd::BddBase};
 
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -251,8 +252,62 @@ impl NafBase {
         println!("vhl.v > goal {goal:?}. descending lo branch with same term");
         self.coeff(term, vhl.lo) }}}
 
-  fn coeff_and(&mut self, _term:&NafTerm, _inv:bool, _x:NID, _y:NID)->NID { todo!("coeff_and"); } // TODO
-  fn coeff_xor(&mut self, _term:&NafTerm, _inv:bool, _x:NID, _y:NID)->NID { todo!("coeff_xor"); } // TODO
+  /// set every variable at or above `floor` that isn't in `keep` to 0.
+  /// (substituting a constant distributes over both `and` and `xor`,
+  /// so this works on the deferred nodes too.)
+  fn zero_vars(&mut self, nid:NID, keep:&[VID], floor:VID)->NID {
+    if nid.is_const() || nid.vid().cmp_depth(&floor) == VidOrdering::Below { return nid }
+    if nid.is_var() {
+      return if keep.contains(&nid.vid()) { nid } else { NID::from_bit(nid.is_inv()) }}
+    match self.get(nid).unwrap() {
+      NAF::Vhl(Vhl{ v, hi, lo }) => {
+        let lo = self.zero_vars(lo, keep, floor);
+        if !keep.contains(&v) { return lo }
+        let hi = self.zero_vars(hi, keep, floor);
+        if hi == O { lo } else { self.vhl(v, hi, lo).nid }},
+      NAF::And { inv, x, y } => {
+        let x = self.zero_vars(x, keep, floor);
+        let y = self.zero_vars(y, keep, floor);
+        let res = self.sub_and(&x, &y);
+        if inv { !res } else { res }},
+      NAF::Xor { inv, x, y } => {
+        let x = self.zero_vars(x, keep, floor);
+        let y = self.zero_vars(y, keep, floor);
+        let res = self.sub_xor(&x, &y);
+        if inv { !res } else { res }}}}
+
+  /// the coefficient of `term` in `x & y` is the sum of (coeff a x)*(coeff b y)
+  /// over every pair of sub-terms a, b whose union is `term`. (since the term
+  /// is never empty here, the `inv` bit only affects the constant and can be ignored.)
+  /// note that this is exponential in the length of the term.
+  fn coeff_and(&mut self, term:&NafTerm, _inv:bool, x:NID, y:NID)->NID {
+    let floor = *term.last().unwrap();
+    let full = (1usize << term.len()) - 1;
+    let (mut xs, mut ys) = (vec![], vec![]);
+    for mask in 0..=full {
+      let sub:NafTerm = term.iter().enumerate()
+        .filter(|(i,_)| mask & (1 << i) != 0).map(|(_,v)| *v).collect();
+      let xz = self.zero_vars(x, &sub, floor); xs.push(self.coeff(&sub, xz));
+      let yz = self.zero_vars(y, &sub, floor); ys.push(self.coeff(&sub, yz)); }
+    let mut res = O;
+    for (a, xa) in xs.into_iter().enumerate() {
+      if xa == O { continue }
+      // b must cover whatever a leaves out, plus any subset of a:
+      let rest = full & !a;
+      let mut s = a;
+      loop {
+        let xy = self.sub_and(&xa, &ys[rest | s]);
+        res = self.sub_xor(&res, &xy);
+        if s == 0 { break }
+        s = (s - 1) & a; }}
+    res }
+
+  /// the coefficient of `term` in `x ^ y` is just the xor of the two coefficients.
+  /// (again, `inv` only affects the constant term)
+  fn coeff_xor(&mut self, term:&NafTerm, _inv:bool, x:NID, y:NID)->NID {
+    let cx = self.coeff(term, x);
+    let cy = self.coeff(term, y);
+    self.sub_xor(&cx, &cy) }
 
   pub fn gather_terms(&mut self, xs:Vec<NID>)->(Vec<NAF>, Vec<NAF>, Vec<NAF>) {
     let mut vhls = vec![];
@@ -269,7 +324,8 @@ impl NafBase {
 
   /// return the coefficient for the given term of the polynomial referred to by `nid`
   pub fn coeff(&mut self, term:&NafTerm, nid:NID)->NID {
-    if nid.is_const() || term.is_empty() { return nid }
+    if term.is_empty() { return nid }
+    if nid.is_const() { return O } // constants only contribute to the empty term
     if nid.is_var() {
       return if term.len() == 1 { if nid.vid() == term[0] { I } else { O }}
       else { O }}
@@ -396,4 +452,95 @@ impl NafBase {
           vec![*x, *y, (if *inv { ops::NXOR } else { ops::XOR }).to_nid()]} })); }
     let top = NID::ixn(res.bits.len()-1);
     let (ast, _new_top) = res.repack(vec![top]);
-    ast }}
\ No newline at end of file
+    ast }
+
+  /// transfer node to a bdd base (without going through the AST), and return the NID from that base.
+  pub fn to_bdd(&self, top:NID, dest:&mut BddBase)->NID {
+    let mut memo = HashMap::new();
+    self.to_bdd_aux(top, dest, &mut memo) }
+
+  fn to_bdd_aux(&self, n:NID, dest:&mut BddBase, memo:&mut HashMap<NID,NID>)->NID {
+    if n.is_const() { return n }
+    // inverting any of the three node types just inverts the whole function:
+    let r = n.raw();
+    let res = if let Some(&res) = memo.get(&r) { res } else {
+      let res = match self.get(r).unwrap() {
+        NAF::Vhl(Vhl{ v, hi, lo }) => {
+          let hi = self.to_bdd_aux(hi, dest, memo);
+          let lo = self.to_bdd_aux(lo, dest, memo);
+          let vhi = dest.and(NID::from_vid(v), hi);
+          dest.xor(vhi, lo) },
+        NAF::And { inv, x, y } => {
+          let (x, y) = (self.to_bdd_aux(x, dest, memo), self.to_bdd_aux(y, dest, memo));
+          let res = dest.and(x, y);
+          if inv { !res } else { res }},
+        NAF::Xor { inv, x, y } => {
+          let (x, y) = (self.to_bdd_aux(x, dest, memo), self.to_bdd_aux(y, dest, memo));
+          let res = dest.xor(x, y);
+          if inv { !res } else { res }}};
+      memo.insert(r, res);
+      res };
+    if n.is_inv() { !res } else { res }}}
+
+// test suite
+#[cfg(test)] use crate::anf::ANFBase;
+
+/// does the ANF polynomial contain the term with every variable up to and including `top`?
+#[cfg(test)] fn anf_has_last_term(anf:&ANFBase, n:NID, top:usize)->bool {
+  let last = (1 << (top+1)) - 1;
+  anf.terms(n).any(|t| t.as_usize() == last) }
+
+#[test] fn test_naf_last_coeff_xor() {
+  let mut naf = NafBase::new(); let mut anf = ANFBase::new();
+  let (x,y,z) = (NID::var(0), NID::var(1), NID::var(2));
+  let n = expr![naf, ((z^(z&y))^((y&x)^x))];
+  let a = expr![anf, ((z^(z&y))^((y&x)^x))];
+  assert_eq!(naf.last_coeff(n), NID::from_bit(anf_has_last_term(&anf, a, 2)));
+  let n = expr![naf, (((z&y)&x)^(y&x))];
+  let a = expr![anf, (((z&y)&x)^(y&x))];
+  assert_eq!(naf.last_coeff(n), I);
+  assert_eq!(naf.last_coeff(n), NID::from_bit(anf_has_last_term(&anf, a, 2)));}
+
+#[test] fn test_naf_last_coeff_and() {
+  let mut naf = NafBase::new(); let mut anf = ANFBase::new();
+  let (a,b,c,d) = (NID::var(0), NID::var(1), NID::var(2), NID::var(3));
+  // each of these leaves deferred And/Xor nodes under the top vhl:
+  let n = expr![naf, ((a^b) & (c^d))];
+  let x = expr![anf, ((a^b) & (c^d))];
+  assert_eq!(naf.last_coeff(n), NID::from_bit(anf_has_last_term(&anf, x, 3)));
+  let n = expr![naf, (((a&b)^c) & ((b&c)^d))];
+  let x = expr![anf, (((a&b)^c) & ((b&c)^d))];
+  assert_eq!(naf.last_coeff(n), NID::from_bit(anf_has_last_term(&anf, x, 3)));
+  let n = expr![naf, (((a&b)^(c&d)) & ((a^c)&(b^d)))];
+  let x = expr![anf, (((a&b)^(c&d)) & ((a^c)&(b^d)))];
+  assert_eq!(naf.last_coeff(n), NID::from_bit(anf_has_last_term(&anf, x, 3)));
+  let n = expr![naf, ((((a&b)&c)&d) ^ (a&b))];
+  assert_eq!(naf.last_coeff(n), I);}
+
+#[test] fn test_naf_coeff_terms() {
+  let mut naf = NafBase::new(); let mut anf = ANFBase::new();
+  let (a,b,c,d) = (NID::var(0), NID::var(1), NID::var(2), NID::var(3));
+  let n = expr![naf, (((a&b)^c) & ((b&c)^d))];
+  let x = expr![anf, (((a&b)^c) & ((b&c)^d))];
+  let terms:Vec<usize> = anf.terms(x).map(|t| t.as_usize()).collect();
+  // every term that ends in x0 has a constant coefficient:
+  for m in (1..16usize).filter(|m| m & 1 == 1) {
+    let term:NafTerm = (0..4).rev().filter(|i| m & (1<<i) != 0).map(|i| VID::var(i as u32)).collect();
+    assert_eq!(naf.coeff(&term, n), NID::from_bit(terms.contains(&m)), "coeff for term {term:?}"); }}
+
+#[test] fn test_naf_to_bdd() {
+  let mut naf = NafBase::new(); let mut anf = ANFBase::new();
+  let mut bdd = BddBase::new();
+  let (a,b,c,d) = (NID::var(0), NID::var(1), NID::var(2), NID::var(3));
+  // (these leave deferred And/Xor nodes in the naf)
+  let ns = [expr![naf, ((a^b) & (c^d))], expr![naf, (((a&b)^c) & ((b&c)^d))],
+            expr![naf, (((a&b)^(c&d)) & ((a^c)&(b^d)))], expr![naf, ((a&b)^I)]];
+  let xs = [expr![anf, ((a^b) & (c^d))], expr![anf, (((a&b)^c) & ((b&c)^d))],
+            expr![anf, (((a&b)^(c&d)) & ((a^c)&(b^d)))], expr![anf, ((a&b)^I)]];
+  for (&n, &x) in ns.iter().zip(xs.iter()) {
+    let expect = anf.to_base(x, &mut bdd);
+    assert_eq!(naf.to_bdd(n, &mut bdd), expect, "naf->bdd should match anf->bdd for {:?}", n);
+    assert_eq!(naf.to_bdd(!n, &mut bdd), !expect, "inverted {:?}", n); }
+  assert_eq!(naf.to_bdd(a, &mut bdd), a);
+  assert_eq!(naf.to_bdd(!c, &mut bdd), !c);
+  assert_eq!(naf.to_bdd(I, &mut bdd), I); }
@@ -1,6 +1,7 @@
 //! Node IDs (shared by various Base implementations)
 use std::fmt;
 use std::str::FromStr;
+use serde::{Serialize, Deserialize};
 use crate::vid;
 
 // -- core data types ---
@@ -8,7 +9,7 @@ use crate::vid;
 /// A NID represents a node in a Base. Essentially, this acts like a tuple
 /// containing a VID and index, but for performance reasons, it is packed into a u64.
 /// See below for helper functions that manipulate and analyze the packed bits.
-#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
 pub struct NID { n: u64 }
 
 /// A truth table stored directly in a nid for functions of up to 5 inputs.
@@ -125,12 +126,12 @@ impl FromStr for NID {
       let (a, b) = if let Some(ix) = word.find('.') { word.split_at(ix) } else { (word, "") };
       let mut ch = a.chars().peekable();
       let mut inv: bool = false;
-      if ch.peek().unwrap() == &'!' { ch.next(); inv = true }
+      if ch.peek() == Some(&'!') { ch.next(); inv = true }
       macro_rules! num_suffix {
         ($radix:expr, $ch:expr) => { usize::from_str_radix(&$ch.collect::<String>(), $radix) }}
-      let c = ch.next().unwrap();
+      let Some(c) = ch.next() else { return Err(format!("empty nid: '{}'", word)) };
       // literals or VHL NIDS:
-      if c == 'x' || 'c'=='v'  {
+      if c == 'x' || c == 'v' {
         if let Ok(n) = num_suffix!(16, ch) {
           let v = if c == 'x' { vid::VID::var(n as u32) } else { vid::VID::vir(n as u32) };
           if b.is_empty() { Ok(NID::from_vid(v).inv_if(inv)) }
@@ -156,14 +157,32 @@ impl FromStr for NID {
               else { 2 };
             Ok(NID::fun(ar, n as u32).to_nid().inv_if(inv))}
           else { Err(format!("bad fun: {}", word)) }
-        't' =>
-            if ch.clone().count() == 4 {
-              if let Ok(tb) = num_suffix!(2, ch) {
+        't' => {
+            // "tb" marks an explicitly binary table, where O and I can stand in for 0 and 1.
+            // Shorter tables are padded on the left, so "t1" is the same as "t0001".
+            let bits:String = if ch.peek() == Some(&'b') {
+              ch.skip(1).map(|c| match c { 'O' => '0', 'I' => '1', _ => c }).collect() }
+              else { ch.collect() };
+            if (1..=4).contains(&bits.len()) {
+              if let Ok(tb) = num_suffix!(2, bits.chars()) {
                 Ok(NID::fun(2, tb as u32).to_nid().inv_if(inv))}
               else { Err(format!("bad table (expect 4 bits): {}", word)) }}
-            else { Err(format!("bad length for table (expect 4 bits): {}", word)) }
+            else { Err(format!("bad length for table (expect 4 bits): {}", word)) }}
         _ => Err(format!("{}?", word))}}}}}}
 
+impl NID {
+  /// parse a nid with an optional namespace prefix (like `"foo:x3"`), for when
+  /// you're juggling nids from several bases. The namespace must be an identifier.
+  pub fn parse_namespaced(word: &str) -> Result<(Option<String>, NID), String> {
+    match word.split_once(':') {
+      None => Ok((None, word.parse()?)),
+      Some((ns, rest)) => {
+        let mut cs = ns.chars();
+        let ident = cs.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+          && cs.all(|c| c.is_alphanumeric() || c == '_');
+        if ident { Ok((Some(ns.to_string()), rest.parse()?)) }
+        else { Err(format!("bad namespace in: {}", word)) }}}}}
+
 
 #[test] fn test_nids() {
   let new = |n| { NID{n} };
@@ -236,8 +255,16 @@ impl NID {
   #[inline(always)] pub fn is_ixn(self)->bool { (self.n & (F|T|VAR) == 0) && vid_bits(self)==NOVAR }
 
   /// Map the NID to an index. (I.e., if n=idx(x), then x is the nth node branching on var(x))
+  /// !! This just returns the low 32 bits, whether or not they mean anything. For constants
+  ///    and literals the bits are always 0, and for function nids they hold the truth table.
+  ///    Use `idx_checked` if you don't already know what kind of nid you have.
   #[inline(always)] pub fn idx(self)->usize { (self.n & IDX_MASK) as usize }
 
+  /// Like `idx`, but only returns the index for nids that actually refer to a stored
+  /// node (ixn or VHL nids). Returns None for constants, literals, and functions.
+  #[inline(always)] pub fn idx_checked(&self)->Option<usize> {
+    if self.is_const() || self.is_vid() || self.is_fun() { None } else { Some(self.idx()) }}
+
   /// Return the NID with the 'INV' flag removed.
   // !! pos()? abs()? I don't love any of these names.
   #[inline(always)] pub fn raw(self)->NID { NID{ n: self.n & !INV }}
@@ -264,4 +291,32 @@ impl NID {
   assert_eq!("t1110", format!("{}", NID::fun(2, 0b1110).to_nid()));
   assert_eq!("f3.FC", format!("{}", NID::fun(3, 0xFC).to_nid()));}
 
+#[test] fn test_tbl_parse() {
+  let t1 = NID::fun(2, 0b0001).to_nid();
+  for s in ["t1", "t0001", "tb0001", "tbOOOI", "tbO0OI"] {
+    assert_eq!(s.parse::<NID>(), Ok(t1), "parsing {}", s) }
+  assert_eq!("t1110".parse::<NID>(), Ok(NID::fun(2, 0b1110).to_nid()));
+  assert_eq!("!tbIOOO".parse::<NID>(), Ok(!NID::fun(2, 0b1000).to_nid()));
+  assert_eq!("f3.FC".parse::<NID>(), Ok(NID::fun(3, 0xFC).to_nid()));
+  assert!("tb00001".parse::<NID>().is_err());
+  assert!("tb".parse::<NID>().is_err());
+  assert!("tb0021".parse::<NID>().is_err()); }
+
+#[test] fn test_parse_namespaced() {
+  assert_eq!(NID::parse_namespaced("foo:x3"), Ok((Some("foo".to_string()), NID::var(3))));
+  assert_eq!(NID::parse_namespaced("x3"), Ok((None, NID::var(3))));
+  assert!(NID::parse_namespaced(":x3").is_err());
+  assert!(NID::parse_namespaced("3a:x3").is_err());
+  assert!(NID::parse_namespaced("foo:").is_err()); }
+
+#[test] fn test_idx_checked() {
+  assert_eq!(O.idx_checked(), None);
+  assert_eq!(I.idx_checked(), None);
+  assert_eq!(NID::var(3).idx_checked(), None);
+  assert_eq!(NID::vir(3).idx_checked(), None);
+  assert_eq!(NID::fun(2, 0b0110).to_nid().idx_checked(), None);
+  assert_eq!(NID::ixn(5).idx_checked(), Some(5));
+  assert_eq!((!NID::ixn(5)).idx_checked(), Some(5));
+  assert_eq!(NID::from_vid_idx(vid::VID::var(2), 7).idx_checked(), Some(7)); }
+
 include!("nid-fun.rs");
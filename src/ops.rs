@@ -1,6 +1,7 @@
 //! Tools for constructing boolean expressions using NIDs as logical operations.
 use crate::{NID, Fun, nid::NidFun, vid::VID};
 use std::slice::Iter;
+use std::{fmt, str::FromStr};
 
 /// A sequence of operations.
 /// Currently, RPN is the only format, but I made this an enum
@@ -39,6 +40,39 @@ impl Ops {
     rpn.push(f.to_nid());
     Ops::RPN(rpn)}}
 
+/// Renders the RPN in bex operator notation, as space-separated tokens:
+/// `*` for and, `+` for or, `%` for xor, and `~` for an inverted argument.
+/// Anything else is written the way `NID` displays it. So `and(x0, !x1)` is `x0 ~x1 *`.
+impl fmt::Display for Ops {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let toks:Vec<String> = self.to_rpn().map(|&n| match n.to_fun() {
+      Some(AND) => "*".to_string(),
+      Some(VEL) => "+".to_string(),
+      Some(XOR) => "%".to_string(),
+      _ => if n.is_inv() && !n.is_const() { format!("~{}", !n) } else { n.to_string() }}).collect();
+    write!(f, "{}", toks.join(" ")) }}
+
+/// Parses the format written by `Display`. (`!` also works for inverted arguments.)
+impl FromStr for Ops {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let nids:Result<Vec<NID>, String> = s.split_whitespace().map(|tok| match tok {
+      "*" => Ok(AND.to_nid()),
+      "+" => Ok(VEL.to_nid()),
+      "%" => Ok(XOR.to_nid()),
+      _ => match tok.strip_prefix('~') {
+        Some(rest) => rest.parse::<NID>().map(|n| !n),
+        None => tok.parse::<NID>() }}).collect();
+    let nids = nids?;
+    // must be exactly one function, at the end, preceded by one argument per input:
+    let (f, args) = nids.split_last().ok_or("empty rpn")?;
+    let f = f.to_fun().ok_or_else(|| format!("rpn should end with a function, not {}", f))?;
+    if let Some(x) = args.iter().find(|x| x.is_fun()) {
+      return Err(format!("expected a single function at the end of the rpn, but found {} in the arguments", x)) }
+    if args.len() != f.arity() as usize {
+      return Err(format!("{} takes {} arguments, but got {}", f.to_nid(), f.arity(), args.len())) }
+    Ok(Ops::RPN(nids)) }}
+
 /// constructor for rpn
 pub fn rpn(xs:&[NID])->Ops { Ops::RPN(xs.to_vec()) }
 
@@ -169,3 +203,20 @@ pub fn imp<X:ToNID,Y:ToNID>(x:X,y:Y)->Ops { rpn(&[x.to_nid(), y.to_nid(), IMP.to
   assert_eq!(2, f.arity());
   assert_eq!(f.tbl() & 0b1111, 0b0100);
   assert_eq!(rpn, vec![NID::var(0), NID::var(1)]);}
+
+#[test] fn test_ops_display_parse() {
+  let (x0, x1, x2) = (NID::var(0), NID::var(1), NID::var(2));
+  let ops = and(x0, x1);
+  assert_eq!(ops.to_string(), "x0 x1 *");
+  assert_eq!(ops.to_string().parse::<Ops>(), Ok(ops));
+  let ops = rpn(&[x0, !x1, x2, ITE.to_nid()]);
+  assert_eq!(ops.to_string(), format!("x0 ~x1 x2 {}", ITE.to_nid()));
+  assert_eq!(ops.to_string().parse::<Ops>(), Ok(ops));
+  assert_eq!("x0 !x1 %".parse::<Ops>(), Ok(xor(x0, !x1)));
+  assert_eq!("x0 I +".parse::<Ops>(), Ok(vel(x0, crate::nid::I)));
+  assert!("x0 ? *".parse::<Ops>().is_err());
+  assert!("".parse::<Ops>().is_err());
+  // anything that parses should be safe to normalize, so malformed rpn is an error:
+  for bad in ["*", "x0 *", "x0 x1", "x0 x1 x2 *", "x0 * x1 *", "x0 x1 * %"] {
+    assert!(bad.parse::<Ops>().is_err(), "{:?} should not parse", bad) }
+  assert_eq!("x0 x1 *".parse::<Ops>().map(|o| o.norm()), Ok(and(x0, x1).norm())); }
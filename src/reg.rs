@@ -62,10 +62,27 @@ impl Reg {
   pub fn is_empty(&self)->bool { self.nbits == 0 }
 
 
+  /// number of bits that are set.
+  pub fn popcount(&self)->usize { self.data.iter().map(|w| w.count_ones() as usize).sum() }
+
+  /// iterate through the values of all bits in the register, starting with bit 0.
+  pub fn iter_bits(&self)->impl Iterator<Item=bool> + '_ { (0..self.nbits).map(move |i| self.get(i)) }
 
   /// build a usize from the least significant bits of the register.
   pub fn as_usize(&self)->usize { self.data[0] }
 
+  /// build a u128 from the register, or None if it has more than 128 bits.
+  pub fn to_u128(&self)->Option<u128> {
+    if self.nbits > 128 { return None }
+    Some(self.data.iter().enumerate().fold(0u128, |acc, (j, &w)| acc | (w as u128) << (j * USIZE))) }
+
+  /// build a register with nvars bits from the low bits of a u128.
+  /// (bits at or above position nvars are ignored.)
+  pub fn from_u128(bits:u128, nvars:usize)->Self {
+    let mut res = Reg::new(nvars);
+    for i in 0..nvars.min(128) { if (bits >> i) & 1 == 1 { res.put(i, true) }}
+    res }
+
   /// build a usize from the least significant bits of the register, in reverse order.
   pub fn as_usize_rev(&self)->usize {
     assert!(self.nbits <= 64, "usize_rev only works for <= 64 bits!");
@@ -99,9 +116,34 @@ impl Reg {
       else { j+=dj }}
     Some(j as usize)}
 
-  /// increment the register as if adding 1.
-  /// return position where the ripple-carry stopped.
-  pub fn increment(&mut self)->Option<usize> { self.ripple(0, self.nbits-1) }
+  /// increment the register as if adding 1, a whole word at a time.
+  /// return position where the ripple-carry stopped (the 0 that became a 1),
+  /// or None on overflow (in which case the register wraps around to 0).
+  pub fn increment(&mut self)->Option<usize> {
+    for j in 0..self.data.len() {
+      let w = self.data[j];
+      let pos = j * USIZE + w.trailing_ones() as usize;
+      if pos >= self.nbits { break }
+      if w != usize::MAX { self.data[j] = w + 1; return Some(pos) }
+      self.data[j] = 0 }
+    self.data.iter_mut().for_each(|w| *w = 0);
+    None }
+
+  /// decrement the register as if subtracting 1, a whole word at a time.
+  /// return position where the borrow stopped (the 1 that became a 0),
+  /// or None on underflow (in which case the register wraps around to all 1s).
+  pub fn decrement(&mut self)->Option<usize> {
+    for j in 0..self.data.len() {
+      let w = self.data[j];
+      let pos = j * USIZE + w.trailing_zeros() as usize;
+      if pos >= self.nbits { break }
+      if w != 0 { self.data[j] = w - 1; return Some(pos) }
+      self.data[j] = usize::MAX }
+    self.data.iter_mut().for_each(|w| *w = usize::MAX);
+    // keep the bits past nbits clear:
+    let extra = self.data.len() * USIZE - self.nbits;
+    if extra > 0 { if let Some(w) = self.data.last_mut() { *w >>= extra }}
+    None }
 
 } // impl Reg
 
@@ -149,9 +191,59 @@ fn test_reg_mut() {
   assert_eq!(None, reg.increment(), "11 -> 00"); }
 
 
+#[test] fn test_reg_inc_words() {
+  let mut reg = Reg::new(70);
+  for i in 0..USIZE { reg.put(i, true) }
+  assert_eq!(reg.increment(), Some(USIZE), "carry should cross into the second word");
+  assert_eq!(reg.hi_bits(), [USIZE]);
+  assert_eq!(reg.decrement(), Some(USIZE), "borrow should cross back into the first word");
+  assert_eq!(reg.hi_bits(), (0..USIZE).collect::<Vec<_>>());
+  // overflow wraps to zero, underflow wraps to all ones (but only within nbits):
+  let mut reg = Reg::from_bits(70, &(0..70).collect::<Vec<_>>());
+  assert_eq!(reg.increment(), None);
+  assert_eq!(reg, Reg::new(70));
+  assert_eq!(reg.decrement(), None);
+  assert_eq!(reg, Reg::from_bits(70, &(0..70).collect::<Vec<_>>())); }
+
+#[test] fn test_reg_count_all() {
+  // walk all 2^n assignments, up and then back down
+  let mut reg = Reg::new(3);
+  let mut seen = vec![reg.as_usize()];
+  while reg.increment().is_some() { seen.push(reg.as_usize()) }
+  assert_eq!(seen, (0..8).collect::<Vec<_>>());
+  assert_eq!(reg.as_usize(), 0);
+  assert_eq!(reg.decrement(), None, "0 -> 111");
+  let mut seen = vec![reg.as_usize()];
+  while reg.decrement().is_some() { seen.push(reg.as_usize()) }
+  assert_eq!(seen, (0..8).rev().collect::<Vec<_>>()); }
+
+#[test] fn test_reg_u128() {
+  let x:u128 = (1 << 100) | (1 << 64) | (1 << 63) | 0b1011;
+  let reg = Reg::from_u128(x, 101);
+  assert_eq!(reg.hi_bits(), [0, 1, 3, 63, 64, 100]);
+  assert_eq!(reg.to_u128(), Some(x));
+  // bits past nvars get dropped:
+  let reg = Reg::from_u128(x, 64);
+  assert_eq!(reg.to_u128(), Some(x & u64::MAX as u128));
+  assert_eq!(Reg::from_u128(u128::MAX, 128).to_u128(), Some(u128::MAX));
+  assert_eq!(Reg::new(129).to_u128(), None); }
+
 #[test] fn test_bits() {
   let ten = Reg::from_bits(4, &[3,1]);
   assert_eq!(ten.as_usize(), 0b1010, "reg with bits 3 and 1 set should equal 10");
   assert_eq!(ten.hi_bits(), [1,3], "bits for 'ten' should come back in order");
   let big = Reg::from_bits(65, &[64,63]);
   assert_eq!(big.hi_bits(), [63,64], "bits for 'big' should come back in order"); }
+
+#[test] fn test_reg_popcount() {
+  let reg = Reg::from_bits(8, &[0, 3, 7]);
+  assert_eq!(reg.popcount(), 3);
+  assert_eq!(reg.iter_bits().collect::<Vec<bool>>(),
+    vec![true, false, false, true, false, false, false, true]);
+  assert_eq!(Reg::new(5).popcount(), 0);
+  // wider than a single word:
+  let reg = Reg::from_bits(130, &[1, 64, 65, 129]);
+  assert_eq!(reg.popcount(), 4);
+  assert_eq!(reg.iter_bits().count(), 130);
+  let hi:Vec<usize> = reg.iter_bits().enumerate().filter(|(_, b)| *b).map(|(i, _)| i).collect();
+  assert_eq!(hi, vec![1, 64, 65, 129]); }
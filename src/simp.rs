@@ -24,3 +24,44 @@ pub fn or(x:NID, y:NID)->Option<NID> {
   else if x == y { Some(x) }
   else if x == !y { Some(I) }
   else { None }}
+
+/// implication: `x / y` is `!x | y`
+pub fn imp(x:NID, y:NID)->Option<NID> {
+  if x == O || y == I || x == y { Some(I) }
+  else if x == I { Some(y) }
+  else if y == O { Some(!x) }
+  else if x == !y { Some(y) }
+  else { None }}
+
+/// equivalence: `x = y` is `!(x ^ y)`
+pub fn eq(x:NID, y:NID)->Option<NID> {
+  if x == y { Some(I) }
+  else if x == !y { Some(O) }
+  else if x == I { Some(y) }
+  else if x == O { Some(!y) }
+  else if y == I { Some(x) }
+  else if y == O { Some(!x) }
+  else { None }}
+
+
+#[test] fn test_simp_imp() {
+  let (x, y) = (NID::var(0), NID::var(1));
+  assert_eq!(imp(O, x), Some(I), "O / x");
+  assert_eq!(imp(I, x), Some(x), "I / x");
+  assert_eq!(imp(x, I), Some(I), "x / I");
+  assert_eq!(imp(x, O), Some(!x), "x / O");
+  assert_eq!(imp(x, x), Some(I), "x / x");
+  assert_eq!(imp(x, !x), Some(!x), "x / !x");
+  assert_eq!(imp(!x, x), Some(x), "!x / x");
+  assert_eq!(imp(x, y), None, "x / y"); }
+
+#[test] fn test_simp_eq() {
+  let (x, y) = (NID::var(0), NID::var(1));
+  assert_eq!(eq(x, x), Some(I), "x = x");
+  assert_eq!(eq(x, !x), Some(O), "x = !x");
+  assert_eq!(eq(I, x), Some(x), "I = x");
+  assert_eq!(eq(O, x), Some(!x), "O = x");
+  assert_eq!(eq(x, I), Some(x), "x = I");
+  assert_eq!(eq(x, O), Some(!x), "x = O");
+  assert_eq!(eq(O, I), Some(O), "O = I");
+  assert_eq!(eq(x, y), None, "x = y"); }
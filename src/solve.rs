@@ -14,7 +14,6 @@ use std::env;
 ///
 /// (the --nocapture is an optional argument to the test engine. it turns off
 /// capturing of stdout so that you can see debug lines from the solver)
-
 use std::{collections::HashSet, time::SystemTime};
 use crate::{apl, ops};
 use crate::base::Base;
@@ -131,14 +130,21 @@ fn default_bitmask(_src:&RawASTBase, v:VID) -> u64 { v.bitmask() }
 /// Sorting your AST this way dramatically reduces the cost of converting to
 /// another form. (For example, the test_tiny benchmark drops from 5282 steps to 111 for BddBase)
 pub fn sort_by_cost(src:&RawASTBase, top:SrcNid)->(RawASTBase,SrcNid) {
-  let (mut src0,kept0) = src.repack(vec![top.n]);
-  src0.tag(kept0[0], "-top-".to_string());
+  let (ast, tops) = sort_by_cost_many(src, &[top]);
+  (ast, tops[0]) }
+
+/// Same as `sort_by_cost`, but keeps every node reachable from any of the given tops.
+pub fn sort_by_cost_many(src:&RawASTBase, tops:&[SrcNid])->(RawASTBase,Vec<SrcNid>) {
+  let (mut src0,kept0) = src.repack(tops.iter().map(|t| t.n).collect());
+  for (i, &k) in kept0.iter().enumerate() { src0.tag(k, format!("-top-{}", i)); }
   // m:mask (which input vars are required?); c:cost (in steps before we can calculate)
   let (_m0,c0) = src0.masks_and_costs(default_bitmask);
   let p = apl::gradeup(&c0); // p[new idx] = old idx
   let ast = src0.permute(&p);
-  let n = ast.get("-top-").expect("what? I just put it there.");
-  (ast,SrcNid{n}) }
+  let tops = (0..tops.len()).map(|i| {
+    let n = ast.get(&format!("-top-{}", i)).expect("what? I just put it there.");
+    SrcNid{n} }).collect();
+  (ast,tops) }
 
 
 /// map a nid from the source to a (usually virtual) variable in the destination
@@ -194,9 +200,28 @@ fn refine_one(dst: &mut dyn SubSolver, v:VID, src:&RawASTBase, d:DstNid)->DstNid
 /// no matter how slow this process is, it will be less slow that trying to fully solve
 /// each intermediate node by working "forward".
 pub fn solve<S:SubSolver>(dst:&mut S, src0:&RawASTBase, sn:NID)->DstNid {
+  solve_with_progress(dst, src0, sn, &mut default_progress()) }
+
+/// the progress reporter used when the caller doesn't supply one.
+fn default_progress<'a>()->ProgressReport<'a> {
+  ProgressReport{ start: SystemTime::now(), save_dot: false, save_dest: false, prefix:"x", millis: 0 }}
+
+/// Same as `solve`, but reports each step to the given `Progress` implementation.
+pub fn solve_with_progress<S:SubSolver>(dst:&mut S, src0:&RawASTBase, sn:NID, pr:&mut dyn Progress<S>)->DstNid {
+  solve_aux(dst, src0, sn, pr, None, None).0 }
+
+/// Same as `solve`, but gives up after `max_steps` substitutions or once the
+/// steps have taken a total of `max_millis` milliseconds (whichever comes first).
+/// Returns the (possibly partially refined) context, and whether the solver finished.
+pub fn solve_with_limits<S:SubSolver>(dst:&mut S, src0:&RawASTBase, sn:NID,
+  max_steps:Option<usize>, max_millis:Option<u128>)->(DstNid, bool) {
+  solve_aux(dst, src0, sn, &mut default_progress(), max_steps, max_millis) }
+
+fn solve_aux<S:SubSolver>(dst:&mut S, src0:&RawASTBase, sn:NID, pr:&mut dyn Progress<S>,
+  max_steps:Option<usize>, max_millis:Option<u128>)->(DstNid, bool) {
   // AST nids don't contain VIR nodes (they "are" vir nodes).
   // If it's already a const or a VID::var, though, there's nothing to do.
-  if sn.is_lit() { DstNid{n:sn} }
+  if sn.is_lit() { (DstNid{n:sn}, true) }
   else {
     dst.init(sn.vid());
     // renumber and garbage collect, leaving only the AST nodes reachable from sn
@@ -220,19 +245,45 @@ pub fn solve<S:SubSolver>(dst:&mut S, src0:&RawASTBase, sn:NID)->DstNid {
     // It begins with just the vir representing the top node in the AST.
     let mut ctx = DstNid{n: dst.init(v)};
 
-    // This just lets us record timing info. TODO: pr probably should be an input parameter.
-    let mut pr = ProgressReport{ start: SystemTime::now(), save_dot: false, save_dest: false, prefix:"x", millis: 0 };
-    <dyn Progress<S>>::on_start(&mut pr, &ctx);
+    pr.on_start(&ctx);
 
     // main loop:
+    let (mut steps, mut total_millis) = (0, 0);
     while !(ctx.n.is_var() || ctx.n.is_const()) {
       let now = std::time::SystemTime::now();
       let old = ctx; ctx = refine_one(dst, v, &src, ctx);
       let millis = now.elapsed().expect("elapsed?").as_millis();
       pr.on_step(&src, dst, step, millis, old, ctx);
-      if step == 0 { break } else { step -= 1; v=VID::vir(step as u32) }}
+      steps += 1; total_millis += millis;
+      if step == 0 { break } else { step -= 1; v=VID::vir(step as u32) }
+      let out_of_steps = max_steps.is_some_and(|m| steps >= m);
+      let out_of_time = max_millis.is_some_and(|m| total_millis >= m);
+      if (out_of_steps || out_of_time) && !(ctx.n.is_var() || ctx.n.is_const()) {
+        pr.on_done(&src, dst, ctx);
+        return (ctx, false) }}
     pr.on_done(&src, dst, ctx);
-    ctx}}
+    (ctx, true) }}
+
+/// Convert several AST roots at once. The roots are sorted together, and each
+/// destination context is seeded with its root's vir node before the substitution
+/// loop, so each virtual variable only gets replaced in one pass over all the
+/// contexts. Any subexpressions the roots have in common become shared nodes in
+/// the destination, instead of being rebuilt for each root. (This needs a `Base`,
+/// since solvers like `SwapSolver` only track a single context.)
+pub fn solve_many<S:Base>(dst:&mut S, src0:&RawASTBase, roots:&[NID])->Vec<DstNid> {
+  let ixns:Vec<SrcNid> = roots.iter().filter(|n| !n.is_lit()).map(|n| SrcNid{n:n.raw()}).collect();
+  if ixns.is_empty() { return roots.iter().map(|&n| DstNid{n}).collect() }
+  let (src, tops) = sort_by_cost_many(src0, &ixns);
+  let mut ctxs:Vec<DstNid> = tops.iter().map(|t| DstNid{n: dst.init(VID::vir(t.n.idx() as u32))}).collect();
+  let top = tops.iter().map(|t| t.n.idx()).max().unwrap();
+  for step in (0..=top).rev() {
+    let v = VID::vir(step as u32);
+    for ctx in ctxs.iter_mut() {
+      if !(ctx.n.is_var() || ctx.n.is_const()) { *ctx = refine_one(dst, v, &src, *ctx) }}}
+  let mut ctxs = ctxs.into_iter();
+  roots.iter().map(|&n|
+    if n.is_lit() { DstNid{n} }
+    else { let DstNid{n:r} = ctxs.next().unwrap(); DstNid{n: if n.is_inv() { !r } else { r }} }).collect() }
 
 
 fn multiplication_bits<T0:BInt, T1:BInt>(k:usize)->(BaseBit, BaseBit) {
@@ -291,6 +342,36 @@ pub fn find_factors<T0:BInt, T1:BInt, S:SubSolver>(dest:&mut S, k:usize, expecte
   use crate::{swap::SwapSolver, int::{X2,X4}};
   find_factors::<X2, X4, SwapSolver>(&mut SwapSolver::new(), 6, vec![(2,3)]); }
 
+/// stopping after one step should leave the tiny problem unsolved.
+#[test] pub fn test_solve_with_limits() {
+  use crate::{bdd::BddBase, int::{X4,X8}};
+  let (lt, eq) = multiplication_bits::<X4,X8>(210);
+  let top:BaseBit = lt & eq;
+  let gb = GBASE.with(|gb| gb.replace(ASTBase::empty()));
+  let (ctx, done) = solve_with_limits(&mut BddBase::new(), gb.raw_ast(), top.n, Some(1), None);
+  assert!(!done, "one step shouldn't be enough to finish");
+  assert!(!ctx.n.is_const(), "context should still be unresolved");
+  let (ctx, done) = solve_with_limits(&mut BddBase::new(), gb.raw_ast(), top.n, None, None);
+  assert!(done); assert!(!ctx.n.is_const(), "210 does have factors"); }
+
+/// a custom progress reporter should see one step per node in the (sorted) AST.
+#[test] pub fn test_solve_with_progress() {
+  use crate::{bdd::BddBase, int::{X2,X4}};
+  #[derive(Default)] struct Counter { steps: usize, done: bool }
+  impl<S:SubSolver> Progress<S> for Counter {
+    fn on_step(&mut self, _src:&RawASTBase, _dest: &mut S, _step:usize, _millis:u128, _oldtop:DstNid, _newtop:DstNid) {
+      self.steps += 1 }
+    fn on_done(&mut self, _src:&RawASTBase, _dest: &mut S, _newtop:DstNid) { self.done = true }}
+  let (lt, eq) = multiplication_bits::<X2,X4>(6);
+  let top:BaseBit = lt & eq;
+  let gb = GBASE.with(|gb| gb.replace(ASTBase::empty()));
+  let (sorted, _) = sort_by_cost(gb.raw_ast(), SrcNid{n:top.n});
+  let mut counter = Counter::default();
+  let res = solve_with_progress(&mut BddBase::new(), gb.raw_ast(), top.n, &mut counter);
+  assert!(counter.done, "on_done should be called");
+  assert_eq!(counter.steps, sorted.len());
+  assert!(!res.n.is_const()); }
+
 /// tiny test case: factor (*/2 3 5 7)=210 into 2 nibbles. The only answer is 14,15.
 #[test] pub fn test_tiny_bdd() {
   use crate::{bdd::BddBase, int::{X4,X8}};
@@ -337,3 +418,23 @@ pub fn find_factors<T0:BInt, T1:BInt, S:SubSolver>(dest:&mut S, k:usize, expecte
   let expected = vec![(1,210), (2,105), ( 3,70), ( 5,42),
                       (6, 35), (7, 30), (10,21), (14,15)];
   find_factors::<X8, X16, SwapSolver>(&mut SwapSolver::new(), 210, expected); }
+
+/// solving related roots together should give the same answers as solving them
+/// separately, but with the common structure shared in the destination.
+#[test] pub fn test_solve_many() {
+  use crate::{bdd::BddBase, int::{X2,X4}};
+  let (lt, eq) = multiplication_bits::<X2,X4>(6);
+  let both:BaseBit = lt & eq.clone();
+  let gb = GBASE.with(|gb| gb.replace(ASTBase::empty()));
+  let roots = [eq.n, both.n, !eq.n, crate::nid::I];
+  let mut many = BddBase::new();
+  let res:Vec<NID> = solve_many(&mut many, gb.raw_ast(), &roots).iter().map(|d| d.n).collect();
+  assert_eq!(res[2], !res[0]);
+  assert_eq!(res[3], crate::nid::I);
+  let mut separate = 0;
+  for i in 0..2 {
+    let mut one = BddBase::new();
+    let n = solve(&mut one, gb.raw_ast(), roots[i]).n;
+    assert_eq!(one.tt(n, 4), many.tt(res[i], 4), "root {} should convert the same either way", i);
+    separate += one.node_count(n); }
+  assert!(many.shared_node_count(&res[..2]) < separate, "the roots should share nodes"); }
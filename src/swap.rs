@@ -6,11 +6,15 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::{fmt, hash::Hash};
+use std::io;
+use serde::{Serialize, Deserialize};
 use crate::base::GraphViz;
 use crate::vid::{VID, NOV, TOP};
 use crate::{solve::SubSolver, reg::Reg, nid::{NID,O}, ops::Ops};
-use crate::swarm::{Swarm,Worker,QID,SwarmCmd,WID};
+use crate::swarm::{Swarm,Worker,QID,SwarmCmd,WID,Cancelled};
+use std::sync::atomic::{AtomicBool, Ordering};
 use crate::Fun;
+use crate::io::{write_versioned, read_versioned};
 
 /// XID: An index-based unique identifier for nodes.
 ///
@@ -40,7 +44,7 @@ use crate::Fun;
 /// We could use pointers instead of array indices, but I want this to be a representation
 /// that can persist on disk, so a simple flat index into an array of XVHLs is fine for me.
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub struct XID { x: i64 }
 impl fmt::Debug for XID {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -73,7 +77,7 @@ struct XHiLo { pub hi: XID, pub lo: XID }
 impl std::ops::Not for XHiLo { type Output = XHiLo; fn not(self)->XHiLo { XHiLo { hi:!self.hi, lo:!self.lo }}}
 impl XHiLo { fn as_tup(&self)->(XID,XID) { (self.hi, self.lo) }}
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub struct XVHL { pub v: VID, pub hi: XID, pub lo: XID }
 impl XVHL {
   fn hilo(&self)->XHiLo { XHiLo { hi:self.hi, lo:self.lo } }
@@ -124,6 +128,15 @@ pub struct XVHLScaffold {
   /// tracks refcount changes that are pending for locked rows ("deferred refcount delta")
   drcd: HashMap<VID,HashMap<XID, i64>> }
 
+/// format version for files written by `XVHLScaffold::save`. (see `io::write_versioned`)
+pub const XVHL_FORMAT_VERSION:u32 = 1;
+
+/// The part of a scaffold that gets written to disk. The rows (and the internal
+/// refcounts) are rebuilt from the vhls on load. External refcounts can't be
+/// derived from anything else, so they're stored separately.
+#[derive(Serialize, Deserialize)]
+struct XVHLSaveData { vids: Vec<VID>, vhls: Vec<XVHL>, ercs: Vec<(XID, usize)> }
+
 // snapshot used for debugging
 thread_local! { static SNAPSHOT : RefCell<XVHLScaffold> = RefCell::new(XVHLScaffold::new()) }
 
@@ -229,6 +242,36 @@ impl XVHLScaffold {
                ixrc.ix, xrc, drc, expect, ixrc.irc)) }}}
       Ok(())}
 
+  /// write the scaffold to disk. (This can't be done in the middle of a regroup.)
+  pub fn save(&self, path:&str)->io::Result<()> {
+    assert!(self.locked.is_empty(), "can't save a scaffold while rows are locked");
+    let ercs = self.rows.values().flat_map(|row| row.hm.values())
+      .filter(|ixrc| ixrc.erc > 0).map(|ixrc| (ixrc.ix, ixrc.erc)).collect();
+    let data = XVHLSaveData{ vids: self.vids.clone(), vhls: self.vhls.clone(), ercs };
+    write_versioned(path, XVHL_FORMAT_VERSION, &data) }
+
+  /// load a scaffold previously written by `save`, rebuilding the rows and refcounts.
+  pub fn load(path:&str)->io::Result<XVHLScaffold> {
+    let XVHLSaveData{ vids, vhls, ercs } = read_versioned(path, XVHL_FORMAT_VERSION, "scaffold")?;
+    let mut res = XVHLScaffold::new();
+    for &v in vids.iter() { res.push(v); }
+    res.vhls = vhls;
+    // count the internal references, then index each live node by its row.
+    let live = |x:&XVHL| x.v != NOV && x.v != TOP;
+    let mut ircs:HashMap<XID, usize> = HashMap::new();
+    for x in res.vhls.iter().filter(|x| live(x)) {
+      *ircs.entry(x.hi.raw()).or_insert(0) += 1;
+      *ircs.entry(x.lo.raw()).or_insert(0) += 1; }
+    let ercs:HashMap<XID, usize> = ercs.into_iter().collect();
+    for (i, x) in res.vhls.iter().enumerate().filter(|(_, x)| live(x)) {
+      let ix = XID{ x: i as i64 };
+      let (irc, erc) = (*ircs.get(&ix).unwrap_or(&0), *ercs.get(&ix).unwrap_or(&0));
+      let row = res.rows.get_mut(&x.v).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+        format!("{}: vhls[{}] refers to {}, which isn't in the scaffold", path, i, x.v)))?;
+      row.hm.insert(x.hilo(), IxRc{ ix, irc, erc }); }
+    res.is_valid().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+    Ok(res) }
+
   pub fn get_ixrc(&self, x:XID)->Option<&IxRc> {
     let XVHL{ v, hi, lo } = self.vhls[x.ix()];
     self.rows[&v].hm.get(&XHiLo{ hi, lo }) }
@@ -308,7 +351,7 @@ impl XVHLScaffold {
         if derc < 0 && (derc + ixrc.erc as i64 ) < 0 { panic!("derc would result in negative refcount")}
         else { ixrc.erc = (ixrc.erc as i64 + derc) as usize; }}
       else { panic!("add_ref_ix warning: entry not found for {:?}", vhl) }}
-    else if ix.raw() == XID_O { return } // ignore refs to XID_O/XID_I for now
+    else if ix.raw() == XID_O { }  // ignore refs to XID_O/XID_I for now
     else { panic!("add_ref_ix warning: row not found for {:?}", vhl.v); }}
 
   /// fetch the XVHL for the given xid (if we know it)
@@ -322,6 +365,19 @@ impl XVHLScaffold {
 
   fn branch_var(&self, x:XID)->VID { self.get(x).unwrap().v }
 
+  /// the set of (raw, non-constant) xids reachable from x, including x itself.
+  fn reachable(&self, x:XID)->HashSet<XID> {
+    let mut seen:HashSet<XID> = HashSet::new();
+    let mut todo = vec![x];
+    while let Some(x) = todo.pop() {
+      if x.is_const() || !seen.insert(x.raw()) { continue }
+      todo.push(self.follow(x, true));
+      todo.push(self.follow(x, false)); }
+    seen }
+
+  /// count the distinct (non-constant) nodes reachable from x, including x itself.
+  pub fn node_count(&self, x:XID)->usize { self.reachable(x).len() }
+
   /// produce the fully expanded "truth table" for a bdd
   /// down to the given row, by building rows of the corresponding
   /// binary tree. xids in the result will either be constants,
@@ -543,17 +599,41 @@ impl XVHLScaffold {
   /// the groups are given in bottom-up order (so groups[0] is on bottom), and should
   /// completely partition the scaffold vids.
   fn regroup(&mut self, groups:Vec<HashSet<VID>>) {
+    self.regroup_cancellable(groups, &AtomicBool::new(false)).expect("regroup can't be cancelled without a token") }
+
+  /// like regroup(), but stops early if the cancel flag gets set. Any swaps already
+  /// in progress are allowed to finish, and then all the rows are returned to the
+  /// scaffold, so it's left in a valid state (just not necessarily the requested order).
+  pub fn regroup_cancellable(&mut self, groups:Vec<HashSet<VID>>, cancel:&AtomicBool)->Result<(), Cancelled> {
+    self.regroup_with_progress(groups, cancel, &mut |_| ()) }
+
+  /// like regroup_cancellable(), but also calls progress with the number of swaps
+  /// completed so far, each time the swarm reports back. (The cancel flag is checked
+  /// right after each call, so progress can be used to decide when to cancel.)
+  pub fn regroup_with_progress(&mut self, groups:Vec<HashSet<VID>>, cancel:&AtomicBool,
+    progress:&mut dyn FnMut(usize))->Result<(), Cancelled> {
+    self.regroup_aux(groups, &mut |swaps| { progress(swaps); cancel.load(Ordering::Relaxed) }) }
+
+  /// should_stop is called between messages with the number of swaps completed so far.
+  fn regroup_aux(&mut self, groups:Vec<HashSet<VID>>, should_stop:&mut dyn FnMut(usize)->bool)->Result<(), Cancelled> {
     assert!(self.locked.is_empty());
     self.complete = HashMap::new();
     self.drcd = HashMap::new();
     self.validate("before regroup()");
     // (var, ix) pairs, where plan is to lift var to row ix
     let plan = self.plan_regroup(&groups);
-    if plan.is_empty() { return }
+    if plan.is_empty() { return Ok(()) }
     let mut swarm: Swarm<Q,R,SwapWorker> = Swarm::new_with_threads(plan.len());
     let mut alarm: HashMap<VID,WID> = HashMap::new();
+    let (mut stopping, mut swaps) = (false, 0);
     let _:Option<()> = swarm.run(|wid,qid,r|->SwarmCmd<Q,()> {
-      match qid {
+      // once cancelled, tell any idle workers to hand back their rows.
+      let mut stops:Vec<(WID,Q)> = vec![];
+      if !stopping && should_stop(swaps) {
+        stopping = true;
+        stops = alarm.drain().map(|(_, w)| (w, Q::Stop)).collect(); }
+      let cmd = match qid {
+        QID::INIT if stopping => SwarmCmd::Pass,
         QID::INIT => { // assign next task to the worker
           let (vu, mut work) =  self.next_regroup_task(&plan);
           if vu == NOV { SwarmCmd::Pass }
@@ -563,8 +643,8 @@ impl XVHLScaffold {
             // TODO: assign extra workers to swaps with more nodes?
             // this also happens when we spawn a new thread to work on a formerly completed vid that got displaced
             _ => SwarmCmd::Pass }}}, // we have more threads than variables to swap.
+        QID::STEP(_) if r.is_none() => SwarmCmd::Pass, // TODO: this wasn't supposed to happen, but then Batch[Init]
         QID::STEP(_) => {
-          if r.is_none() { return SwarmCmd::Pass } // TODO: this wasn't supposed to happen, but then Batch[Init]
           match r.unwrap() {
 
             R::DRcD{vu} => {
@@ -576,7 +656,8 @@ impl XVHLScaffold {
 
             // complete one swap in the move:
             R::PutRD{vu, vd, rd, dnew, umov, dels, refs} => {
-              self.swarm_put_rd(&plan, &mut alarm, wid, vu, vd, rd, dnew, umov, dels, refs) },
+              swaps += 1;
+              self.swarm_put_rd(&plan, &mut alarm, stopping, wid, vu, vd, rd, dnew, umov, dels, refs) },
 
             // finish the move for this vid
             R::PutRU{vu, ru} => {
@@ -587,17 +668,34 @@ impl XVHLScaffold {
               self.apply_drcd(&vu);
               self.complete.insert(vu, wid);
 
-              if self.complete.len() == plan.len() {
+              if stopping {
+                // anyone waiting on this row has to stop too (it isn't going to move any more).
+                if let Some(w2) = alarm.remove(&vu) { SwarmCmd::Batch(vec![(w2, Q::Stop)]) }
+                else { SwarmCmd::Pass }}
+              else if self.complete.len() == plan.len() {
                 debug_assert!(alarm.is_empty(), "last worker died but we still have alarms: {:?}", alarm);
                 SwarmCmd::Return(()) }
               else { SwarmCmd::Pass }}}},
 
-        QID::DONE => { SwarmCmd::Pass }}});
+        QID::DONE => { SwarmCmd::Pass }};
+      if stopping {
+        // we're done once every row has been returned.
+        if self.locked.is_empty() { return SwarmCmd::Return(()) }
+        match cmd {
+          SwarmCmd::Pass => if stops.is_empty() { cmd } else { SwarmCmd::Batch(stops) },
+          SwarmCmd::Send(q) => { stops.push((wid, q)); SwarmCmd::Batch(stops) },
+          SwarmCmd::Batch(mut work) => { work.extend(stops); SwarmCmd::Batch(work) },
+          _ => cmd }}
+      else { cmd }});
 
+        debug_assert!(self.locked.is_empty());
+        if stopping {
+          self.validate("after cancelled regroup()");
+          return Err(Cancelled) }
         let plan2 = self.plan_regroup(&groups);
         debug_assert!(plan2.is_empty(), "regroup failed to make these moves: {:?}", plan2);
-        debug_assert!(self.locked.is_empty());
-        self.validate("after regroup()"); }
+        self.validate("after regroup()");
+        Ok(()) }
 
 
   // like add_ref_ix but defers if row is locked.
@@ -617,7 +715,7 @@ impl XVHLScaffold {
 
   /// called whenever a worker returns a downward-moving row to the scaffold
   #[allow(clippy::too_many_arguments)] // TODO fix this!
-  fn swarm_put_rd(&mut self, plan:&HashMap<VID,usize>, alarm:&mut HashMap<VID,WID>,
+  fn swarm_put_rd(&mut self, plan:&HashMap<VID,usize>, alarm:&mut HashMap<VID,WID>, stopping:bool,
     wid:WID, vu:VID, vd:VID, rd:XVHLRow, dnew:Vec<Mod>, umov:Vec<Mod>, dels:Vec<XID>, refs:HashMap<XID,i64>
   )->SwarmCmd<Q,()> {
     // replace and unlock the downward-moving row:
@@ -659,24 +757,26 @@ impl XVHLScaffold {
     // tell anyone waiting on rd that they can resume work
     debug_assert!(!alarm.contains_key(&vd), "alarm should never be placed on a downward-moving row.");
     if let Some(w2) = alarm.remove(&vu) {
+      // if we're cancelling, the waiting worker can just give its row back.
+      if stopping { work.push((w2, Q::Stop)) } else {
       // println!("\x1b[35mTRIGGERED ALARM ON vu:{}, sending vd:{}\x1b[0m", vu, vd);
       // wake the sleeping worker right behind us:
       let rd = self.take_row(&vd).unwrap();
-      work.push((w2, Q::Step{vd, rd})); }
+      work.push((w2, Q::Step{vd, rd})); }}
 
     // vids within the same group will never swap with each other, but vids from different groups may.
     // if vu just moved into vd's planned spot, it means vd's move was already complete, and we just displaced it.
     // However, its worker is already dead (so we need a new one), and the row above is locked until we finish
     // the next move for vu (so we set an alarm rather than spawning a new thread)
-    else if plan.contains_key(&vd) && self.complete.contains_key(&vd) {
+    else if !stopping && plan.contains_key(&vd) && self.complete.contains_key(&vd) {
       // println!("RE-SPAWNING WORKER FOR DISPLACED VID: {}", vd);
       let w = self.complete.remove(&vd).unwrap();
       work.push((w, Q::Init{ vu:vd, ru: self.take_row(&vd).unwrap() }));
       // the alarm goes on the upward-moving row
       alarm.insert(vu, w); }
 
-    // are we there yet? :)
-    if new_uix == plan[&vu] { work.push((wid, Q::Stop)); }
+    // are we there yet? :)  (or should we stop anyway?)
+    if stopping || new_uix == plan[&vu] { work.push((wid, Q::Stop)); }
     else { // start or schedule the next swap
       let vd = self.vid_above(vu).unwrap();
       if let Some(rd) = self.take_row(&vd) { work.push((wid, Q::Step{vd, rd})); }
@@ -707,9 +807,10 @@ enum R {
 // -- graphviz ----------------------------------------------------------
 
 impl GraphViz for XVHLScaffold {
-  fn write_dot(&self, _:NID, wr: &mut dyn std::fmt::Write) {
-    // TODO: show only the given nid, instead of the whole scaffold
-    // assert_eq!(o, NID::o(), "can't visualize individual nids yet. pass O for now");
+  /// draw the nodes reachable from n. (Pass O to draw the whole scaffold.)
+  fn write_dot(&self, n:NID, wr: &mut dyn std::fmt::Write) {
+    let keep = if n == O { None } else { Some(self.reachable(XID::from_nid(n))) };
+    let shown = |x:&XID| keep.as_ref().is_none_or(|k| k.contains(x));
     macro_rules! w { ($x:expr $(,$xs:expr)*) => { writeln!(wr, $x $(,$xs)*).unwrap() }}
     w!("digraph XVHL {{");
     w!("subgraph head {{ h1[shape=plaintext; label=\"XVHL\"] }}");
@@ -718,12 +819,12 @@ impl GraphViz for XVHLScaffold {
     w!("  XI[label=⊤; shape=square];");
     w!("node[shape=circle];");
     for ev in self.vids.iter().rev() {
-      let row = &self.rows[ev];
-      if !row.hm.is_empty() {
+      let row:Vec<(&XHiLo, &IxRc)> = self.rows[ev].hm.iter().filter(|(_, ixrc)| shown(&ixrc.ix)).collect();
+      if !row.is_empty() {
         write!(wr, "{{rank=same").unwrap();
-        for ixrc in row.hm.values() { write!(wr, " \"{:?}\"", ixrc.ix).unwrap() }
+        for (_, ixrc) in row.iter() { write!(wr, " \"{:?}\"", ixrc.ix).unwrap() }
         w!("}}") }
-      for (hl,ixrc) in row.hm.iter() {
+      for (hl,ixrc) in row {
         let x = ixrc.ix;
         w!("  \"{:?}\"[label=\"{}\"];", x, ev);  // draw the node itself
         let arrow = |n:XID| if n.is_const() || !n.is_inv() { "normal" } else { "odot" };
@@ -1230,7 +1331,53 @@ impl SwapSolver {
 
     // 7. return result
     // self.dst.add_eref_ix(self.dx, -1); (except it's already 0 because of the beheading)
-    self.dx }} // sub, SwapSolver
+    self.dx }
+
+  /// Copy the nodes under `ctx` into a fresh BddBase. Returns the base, the bdd node
+  /// corresponding to ctx, and the permutation vector needed to map solutions back
+  /// to the original `nvars` input variables.
+  fn to_bdd(&self, ctx: NID, nvars: usize)->(crate::bdd::BddBase, NID, Vec<usize>) {
+
+    // TODO: prove that we're only copying the nodes directly reachable from xctx.
+    // Proper garbage collection should be sufficient for this.
+
+    self.dst.validate("before to_bdd");
+
+    // Copy from the scaffold to the BDD Base.
+    let mut x2n:HashMap<XID,NID> = HashMap::new();
+    x2n.insert(XID_O, O);
+
+    // copy each row over, from bottom to top...
+    // vids[i] in the scaffold becomes var(i) in the bdd.
+    // (ctx itself might not have any references, or be on the top row, so always keep it.)
+    let xctx = XID::from_nid(ctx);
+    let mut bdd = crate::bdd::BddBase::new();
+    for (i,rv) in self.dst.vids.iter().enumerate() {
+      let bv = NID::from_vid(VID::var(i as u32));
+      for (x, ixrc) in self.dst.rows[rv].hm.iter() {
+        if ixrc.rc() > 0 || ixrc.ix == xctx.raw() || *rv == self.dst.top_vid().unwrap() {
+          let nx = |x:XID|->NID { if x.is_inv() { !x2n[&!x] } else { x2n[&x] }};
+          let (hi, lo) = (nx(x.hi), nx(x.lo));
+          // !! row pairs are never inverted, so we shouldn't have to mess with inv() (... right??)
+          x2n.insert(ixrc.ix, bdd.ite(bv, hi, lo)); }}}
+
+    // Now the base solutions back to the original input ordering.
+    // Each solution `Reg` contains one bit per input var.
+    // To map it back to problem-land:  problem_var[i] = solution_var[self.vix(var(i))]
+    // "pv" actually stands for permutation vector, but problem var works too. :)
+    let n = nvars.max(self.dst.vids.len());
+    let mut pv:Vec<Option<usize>> = vec![None; n];
+    for (i,v) in self.dst.vids.iter().enumerate() { pv[v.var_ix()] = Some(i); }
+
+    // The problem may have collapsed from n vars to n-k vars along the way, so some
+    // inputs may not appear in the scaffold at all. The bdd doesn't depend on the
+    // vars above the scaffold, so map each discarded input to one of those. This way,
+    // solutions_pad() treats them as "don't care" bits and enumerates both values.
+    let mut extra = self.dst.vids.len()..;
+    let pv:Vec<usize> = pv.into_iter().map(|x| x.unwrap_or_else(|| extra.next().unwrap())).collect();
+
+    let nctx = if xctx.is_inv() { !x2n[&!xctx] } else { x2n[&xctx] };
+    (bdd, nctx, pv)}} // impl SwapSolver
 
 
 fn fun_tbl(n:NID)->Vec<XID> {
@@ -1269,43 +1416,14 @@ impl SubSolver for SwapSolver {
     self.rv = v;
     self.sub().to_nid()}
 
-  fn get_all(&self, ctx: NID, nvars: usize)->HashSet<Reg> {
-
-    // TODO: prove that we're only copying the nodes directly reachable from xctx.
-    // Proper garbage collection should be sufficient for this.
-
-    self.dst.validate("before get_all");
-
-    // Copy from the scaffold to the BDD Base.
-    let mut x2n:HashMap<XID,NID> = HashMap::new();
-    x2n.insert(XID_O, O);
-
-    // copy each row over, from bottom to top...
-    // vids[i] in the scaffold becomes var(i) in the bdd.
-    let mut bdd = crate::bdd::BddBase::new();
-    for (i,rv) in self.dst.vids.iter().enumerate() {
-      let bv = NID::from_vid(VID::var(i as u32));
-      for (x, ixrc) in self.dst.rows[rv].hm.iter() {
-        if ixrc.rc() > 0 || *rv == self.dst.top_vid().unwrap() {
-          let nx = |x:XID|->NID { if x.is_inv() { !x2n[&!x] } else { x2n[&x] }};
-          let (hi, lo) = (nx(x.hi), nx(x.lo));
-          // !! row pairs are never inverted, so we shouldn't have to mess with inv() (... right??)
-          x2n.insert(ixrc.ix, bdd.ite(bv, hi, lo)); }}}
-
-    // Now the base solutions back to the original input ordering.
-    // Each solution `Reg` contains one bit per input var.
-    // To map it back to problem-land:  problem_var[i] = solution_var[self.vix(var(i))]
-    // "pv" actually stands for permutation vector, but problem var works too. :)
-    let mut pv:Vec<usize> = vec![0;self.dst.vids.len()];
-    for (i,v) in self.dst.vids.iter().enumerate() { pv[v.var_ix()] = i; }
-
-    // TODO: fill in extra problem vars that got removed from the final scaffold.
-    // !! It may be the case that the problem collapsed from n vars to n-k vars, but
-    //    we still need the solution to be in terms of all n vars... Alternately, the
-    //    SubSolver protocol could have an output field for discarded inputs.
+  fn get_one(&self, ctx: NID, nvars: usize)->Option<Reg> {
+    if ctx == O { return None }
+    let (bdd, nctx, pv) = self.to_bdd(ctx, nvars);
+    bdd.first_solution_reg(nctx, nvars).map(|reg| reg.permute_bits(&pv)) }
 
+  fn get_all(&self, ctx: NID, nvars: usize)->HashSet<Reg> {
+    let (bdd, nctx, pv) = self.to_bdd(ctx, nvars);
     let mut res:HashSet<Reg> = HashSet::new();
-    let nctx = x2n[&XID::from_nid(ctx)];
     for reg in bdd.solutions_pad(nctx, nvars) { res.insert(reg.permute_bits(&pv)); }
     res}
 
@@ -1,6 +1,7 @@
 //! mini-framework for multicore programming.
 use std::{marker::PhantomData, thread};
 use std::sync::mpsc::{Sender, Receiver, channel, SendError, RecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::fmt::Debug;
 use std::collections::HashMap;
 use rand::seq::SliceRandom;
@@ -79,6 +80,10 @@ pub trait Worker<Q,R,I=()> where R:Debug, Q:Clone {
   /// Override this if you need to send a message to the swarm after the work loop finishes.
   fn work_done(&mut self)->Option<R> { None }}
 
+/// returned when a swarm's work was cancelled before it finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
 #[derive(Debug)]
 pub enum SwarmCmd<Q:Debug,V:Debug> {
   Pass,
@@ -187,10 +192,18 @@ impl<Q,R,W,I> Swarm<Q,R,W,I> where Q:'static+Send+Debug+Clone, R:'static+Send+De
       .expect("failed to sent_self"); }
 
   /// pass in the swarm dispatch loop
-  pub fn run<F,V>(&mut self, mut on_msg:F)->Option<V>
+  pub fn run<F,V>(&mut self, on_msg:F)->Option<V>
+    where V:Debug, F:FnMut(WID, &QID, Option<R>)->SwarmCmd<Q,V> {
+    self.run_cancellable(&AtomicBool::new(false), on_msg).expect("swarm can't be cancelled without a token") }
+
+  /// like run(), but checks the cancel flag between messages, and stops as soon
+  /// as it's set. Any work still in progress is abandoned, so if the workers hold
+  /// state you need back, check the flag inside on_msg and wind them down yourself.
+  pub fn run_cancellable<F,V>(&mut self, cancel:&AtomicBool, mut on_msg:F)->Result<Option<V>, Cancelled>
     where V:Debug, F:FnMut(WID, &QID, Option<R>)->SwarmCmd<Q,V> {
     let mut res = None;
     loop {
+      if cancel.load(Ordering::Relaxed) { return Err(Cancelled) }
       if let Ok(q) = self.qrx.try_recv() { self.add_query(q); }
       if let Ok(rmsg) = self.rx.try_recv() {
         let RMsg { wid, qid, r } = rmsg;
@@ -203,4 +216,4 @@ impl<Q,R,W,I> Swarm<Q,R,W,I> where Q:'static+Send+Debug+Clone, R:'static+Send+De
           SwarmCmd::Batch(wqs) => for (wid, q) in wqs { self.send(wid, q); },
           SwarmCmd::Panic(msg) => panic!("{}", msg),
           SwarmCmd::Return(v) => { res = Some(v); break }}}}
-      res}}
+      Ok(res)}}
@@ -1,6 +1,7 @@
 // generic Base test suite
 test_base_consts!(BddBase);
 test_base_when!(BddBase);
+test_base_imp_iff!(BddBase);
 
 #[cfg(test)]
 use  std::iter::FromIterator; use std::hash::Hash;
@@ -157,3 +158,740 @@ fn hs<T: Eq+Hash>(xs: Vec<T>)->HashSet<T> { <HashSet<T>>::from_iter(xs) }
   // but the concept should still work:
   let nx0 = cache.insert(x0, hl);
   assert_eq!(nx0, NID::from_vid_idx(x0, 0));}
+
+#[test] fn test_bdd_atleast() {
+  nid_vars![x0, x1, x2];
+  let mut base = BddBase::new();
+  let vs = [x0, x1, x2];
+  let n = base.atleast(&vs, 2);
+  assert_eq!(base.solutions_pad(n, 3).count(), 4);
+  assert_eq!(vec![0,0,0,1,0,1,1,1], base.tt(n, 3));
+  let n = base.atmost(&vs, 1);
+  assert_eq!(vec![1,1,1,0,1,0,0,0], base.tt(n, 3));
+  let n = base.exactly(&vs, 1);
+  assert_eq!(vec![0,1,1,0,1,0,0,0], base.tt(n, 3));
+  assert_eq!(I, base.atleast(&vs, 0));
+  assert_eq!(O, base.atleast(&vs, 4)); }
+
+#[test] fn test_bdd_ult() {
+  let mut base = BddBase::new();
+  let a:Vec<NID> = (0..3).map(NID::var).collect();
+  let b:Vec<NID> = (3..6).map(NID::var).collect();
+  let lt = base.ult(&a, &b);
+  assert_eq!(base.solutions_pad(lt, 6).count(), 28); // (8*8-8)/2 pairs with a<b
+  for s in base.solutions_pad(lt, 6) {
+    let x = s.as_usize(); assert!(x & 7 < x >> 3, "expected a<b in {:?}", s) }
+  let le = base.ule(&a, &b);
+  assert_eq!(base.solutions_pad(le, 6).count(), 36);
+  let eq = base.ueq(&a, &b);
+  assert_eq!(base.solutions_pad(eq, 6).count(), 8); }
+
+#[test] fn test_bdd_load_version_mismatch() {
+  let mut base = BddBase::new();
+  let (x0, x1) = (NID::var(0), NID::var(1));
+  let n = base.and(x0, x1);
+  base.tag(n, "n".to_string());
+  let path = std::env::temp_dir().join("bex-test-bdd-version.bdd");
+  let path = path.to_str().unwrap();
+  base.save(path).expect("failed to save");
+  assert!(BddBase::load(path).is_ok());
+  // overwrite the version number at the start of the file:
+  let mut bytes = std::fs::read(path).unwrap();
+  bytes[..4].copy_from_slice(&(BDD_FORMAT_VERSION+1).to_le_bytes());
+  std::fs::write(path, bytes).unwrap();
+  let err = BddBase::load(path).expect_err("should reject a newer format version");
+  assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+  assert!(err.to_string().contains("format version"), "{}", err);
+  std::fs::remove_file(path).unwrap(); }
+
+#[test] fn test_bdd_op_log() {
+  nid_vars![x0, x1, x2, x3];
+  let mut base = BddBase::new_with_threads(1);
+  base.enable_op_log();
+  let a = base.and(x0, x1);
+  let b = base.xor(x2, x3);
+  let c = base.or(a, b);
+  let d = base.ite(x3, c, !a);
+  assert!(!base.op_log().is_empty());
+  let mut copy = BddBase::new_with_threads(1);
+  for &(f, g, h, n) in base.op_log() { assert_eq!(copy.ite(f, g, h), n) }
+  assert_eq!(copy.tt(d, 4), base.tt(d, 4));
+  let (old, new):(Vec<_>, Vec<_>) = (base.iter_nodes().collect(), copy.iter_nodes().collect());
+  assert_eq!(old, new); }
+
+#[test] fn test_bdd_divide_by_var() {
+  nid_vars![x0, x1, x2];
+  let mut base = BddBase::new();
+  let v = x1.vid();
+  let a = base.and(x1, x0);
+  let b = base.or(x1, x2);
+  let c = base.or(a, x2);
+  for &n in &[a, b, c, x0, x1, I, O] {
+    let (q, r) = base.divide_by_var(n, v);
+    for &x in &[q, r] {
+      assert_eq!(base.when_hi(v, x), base.when_lo(v, x), "{} should not depend on {}", x, v) }
+    let vq = base.and(x1, q);
+    assert_eq!(base.or(vq, r), n, "failed to reconstruct {}", n) }}
+
+#[test] #[should_panic]
+fn test_bdd_divide_by_var_not_unate() {
+  nid_vars![x0, x1];
+  let mut base = BddBase::new();
+  let n = base.and(!x1, x0);
+  base.divide_by_var(n, x1.vid()); }
+
+#[test] fn test_bdd_restrict_dc() {
+  nid_vars![x0, x1, x2, x3, x4, x5];
+  let mut base = BddBase::new();
+  let onset = expr![base, (((x0 ^ x1) ^ (x2 ^ x3)) ^ (x4 ^ x5))];
+  let hi3 = base.and(x3, x4);
+  let care = base.and(hi3, x5);
+  let res = base.restrict_dc(onset, care);
+  assert!(base.equiv_under(res, onset, care));
+  assert!(base.node_count(res) < base.node_count(onset),
+    "expected {} to have fewer than {} nodes", base.node_count(res), base.node_count(onset));
+  // with no don't-cares, nothing should change:
+  assert_eq!(base.restrict_dc(onset, I), onset); }
+
+#[test] fn test_bdd_solution_count() {
+  nid_vars![x0, x1, x2, x3];
+  let mut base = BddBase::new();
+  let nx2 = !x2;
+  let n = expr![base, ((x0 & x1) ^ (x3 & nx2))];
+  for nvars in 4..8 {
+    assert_eq!(base.solution_count(n, nvars), base.solutions_pad(n, nvars).count() as u64) }
+  assert_eq!(base.solution_count(x1, 2), 2);
+  assert_eq!(base.solution_count(!x1, 3), 4);
+  assert_eq!(base.solution_count(I, 3), 8);
+  assert_eq!(base.solution_count(O, 3), 0); }
+
+#[cfg(test)]
+#[test] fn test_bdd_exists() {
+  nid_vars![x0, x1, x2, x3];
+  let mut base = BddBase::new();
+  let a = base.and(x0, x1);
+  let b = base.and(x2, !x3);
+  let n = base.and(a, b);
+  let (v0, v2, v3) = (x0.vid(), x2.vid(), x3.vid());
+  assert_eq!(base.exists(&HashSet::new(), n), n);
+  assert_eq!(base.exists(&hs(vec![v0]), x1), x1, "x0 isn't in the support of x1");
+  let e = base.exists(&hs(vec![v2, v3]), n);
+  assert_eq!(e, a);
+  assert_eq!(base.support(e), hs(vec![x0.vid(), x1.vid()]));
+  let e = base.exists(&hs(vec![v0, v3]), n);
+  assert_eq!(e, base.and(x1, x2));
+  let x = base.xor(x0, x3);
+  assert_eq!(base.exists(&hs(vec![v0]), x), I); }
+
+#[test] fn test_bdd_permute_inputs() {
+  nid_vars![x0, x1, x2];
+  let mut base = BddBase::new();
+  let (v0, v1, v2) = (x0.vid(), x1.vid(), x2.vid());
+  let swap01:HashMap<VID,VID> = [(v0,v1), (v1,v0)].iter().cloned().collect();
+  let rot:HashMap<VID,VID> = [(v0,v1), (v1,v2), (v2,v0)].iter().cloned().collect();
+  // non-symmetric: x0 & !x1
+  let n = base.and(x0, !x1);
+  let p = base.permute_inputs(n, &swap01);
+  assert_eq!(vec![0,1,0,0,0,1,0,0], base.tt(n, 3));
+  assert_eq!(vec![0,0,1,0,0,0,1,0], base.tt(p, 3));
+  assert_eq!(p, base.and(x1, !x0));
+  let r = base.permute_inputs(n, &rot);
+  assert_eq!(r, base.and(x1, !x2));
+  // symmetric functions don't change:
+  let s = base.atleast(&[x0, x1, x2], 2);
+  assert_eq!(base.permute_inputs(s, &swap01), s);
+  assert_eq!(base.permute_inputs(s, &rot), s); }
+
+#[test] fn test_bdd_forall() {
+  nid_vars![x0, x1, x2];
+  let mut base = BddBase::new();
+  let all = hs(vec![x0.vid(), x1.vid(), x2.vid()]);
+  let x = expr![base, ((x0 ^ x1) ^ x2)];
+  assert_eq!(base.forall(&all, x), O);
+  let t = base.or(x0, !x0);
+  assert_eq!(base.forall(&all, t), I);
+  assert_eq!(base.forall(&all, I), I);
+  // x0 | (x1 & x2) holds for all x1,x2 only when x0 holds:
+  let a = base.and(x1, x2);
+  let n = base.or(x0, a);
+  assert_eq!(base.forall(&hs(vec![x1.vid(), x2.vid()]), n), x0);
+  assert_eq!(base.forall(&HashSet::new(), n), n); }
+
+#[test] fn test_bdd_compose() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3, x4];
+  let ctx = base.ite(x0, x1, x2);
+  let g = base.and(x3, x4);
+  let res = base.compose(ctx, x0.vid(), g);
+  // expected: (x3 & x4) ? x1 : x2, built by hand
+  let t = base.and(g, x1);
+  let e = base.and(!g, x2);
+  let expected = base.or(t, e);
+  assert_eq!(res, expected);
+  assert_eq!(base.compose(ctx, x0.vid(), g), res, "memoized result should match");
+  // g overlapping ctx's support: x1 := x1 & x2
+  let g2 = base.and(x1, x2);
+  let res2 = base.compose(ctx, x1.vid(), g2);
+  let t2 = base.and(x0, g2);
+  let e2 = base.and(!x0, x2);
+  let expected2 = base.or(t2, e2);
+  assert_eq!(res2, expected2); }
+
+#[test] fn test_bdd_windowed_count() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let x02 = base.or(x0, x2);
+  let x13 = base.and(x1, x3);
+  let f = base.and(x02, x13); // x1 & x3 & (x0 | x2)
+  // window [1,2]: projecting out x0 and x3 leaves x1, so x2 is free: 2 of 4
+  assert_eq!(base.windowed_count(f, 1, 2), 2);
+  // window [0,1]: projecting out x2 and x3 leaves x1: 2 of 4
+  assert_eq!(base.windowed_count(f, 0, 1), 2);
+  // window [1,3]: projecting out x0 leaves x1 & x3: 2 of 8
+  assert_eq!(base.windowed_count(f, 1, 3), 2);
+  // window [2,2]: x2 can take either value
+  assert_eq!(base.windowed_count(f, 2, 2), 2);
+  // the full window is just the solution count
+  assert_eq!(base.windowed_count(f, 0, 3), base.solution_count(f, 4));
+  assert_eq!(base.windowed_count(O, 0, 3), 0);
+  assert_eq!(base.windowed_count(I, 1, 2), 4); }
+
+#[test] fn test_bdd_count_in_range() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let x01 = base.xor(x0, x1);
+  let f = base.or(x01, x3);
+  let vars:Vec<VID> = [x0, x1, x2, x3].iter().map(|x| x.vid()).collect();
+  // brute force: plug in each value and see whether f holds
+  let brute = |base:&mut BddBase, f:NID, lo:u64, hi:u64| (lo..hi).filter(|&k| {
+    let a:HashMap<VID,bool> = vars.iter().enumerate().map(|(i, &v)| (v, (k >> i) & 1 == 1)).collect();
+    base.restrict(f, &a) == I }).count() as u64;
+  let expect = brute(&mut base, f, 3, 7);
+  assert_eq!(expect, 2, "just 5 (x0 x2) and 6 (x1 x2)");
+  assert_eq!(base.count_in_range(f, &vars, 3, 7), expect);
+  assert_eq!(base.count_in_range(I, &vars, 3, 7), 4);
+  assert_eq!(base.count_in_range(f, &vars, 0, 16), base.solution_count(f, 4));
+  assert_eq!(base.count_in_range(f, &vars, 7, 3), 0);
+  for (lo, hi) in [(0, 1), (5, 6), (2, 15), (9, 16)] {
+    let expect = brute(&mut base, f, lo, hi);
+    assert_eq!(base.count_in_range(f, &vars, lo, hi), expect, "range {}..{}", lo, hi) }}
+
+#[test] fn test_bdd_weighted_count() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let a = base.and(x0, !x2);
+  let b = base.xor(x1, x3);
+  let n = base.or(a, b);
+  // uniform weights of 1/2 give the probability of a random assignment being a solution:
+  let half:HashMap<VID,(f64,f64)> = (0..5).map(|i| (VID::var(i), (0.5, 0.5))).collect();
+  for nvars in [4, 5] {
+    let expect = base.solution_count(n, nvars) as f64 / (1u64 << nvars) as f64;
+    assert!((base.weighted_count(n, &half, nvars) - expect).abs() < 1e-12); }
+  // with no weights, it's just the solution count:
+  assert_eq!(base.weighted_count(n, &HashMap::new(), 4), base.solution_count(n, 4) as f64);
+  // by hand: P(x0 & !x2) where P(x0)=0.3, P(x2)=0.6 (and x1 is skipped, with weight 1+1)
+  let w = HashMap::from([(x0.vid(), (0.3, 0.7)), (x2.vid(), (0.6, 0.4))]);
+  assert!((base.weighted_count(a, &w, 3) - 0.3*0.4*2.0).abs() < 1e-12);
+  assert_eq!(base.weighted_count(O, &w, 3), 0.0); }
+
+#[test] fn test_bdd_from_dimacs() {
+  let dir = std::env::temp_dir();
+  // (x1 | x2) & (!x1 | x3), with the second clause split across lines
+  let sat = dir.join("bex-test-sat.cnf");
+  std::fs::write(&sat, "c a tiny satisfiable instance\np cnf 3 2\n1 2 0\n-1\n 3 0\n").unwrap();
+  let (base, n) = BddBase::from_dimacs(sat.to_str().unwrap()).expect("failed to read cnf");
+  assert_eq!(base.solution_count(n, 3), 4);
+  // x1 & !x1
+  let unsat = dir.join("bex-test-unsat.cnf");
+  std::fs::write(&unsat, "c unsatisfiable\np cnf 1 2\n1 0\n-1 0\n").unwrap();
+  let (base, n) = BddBase::from_dimacs(unsat.to_str().unwrap()).expect("failed to read cnf");
+  assert_eq!(n, O);
+  assert_eq!(base.solution_count(n, 1), 0); }
+
+#[test] fn test_bdd_onehot() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let n = base.onehot(&[x0, x1, x2, x3]);
+  assert_eq!(base.solution_count(n, 4), 4);
+  let group = [0, 1, 2, 3];
+  let mut seen = HashSet::new();
+  for reg in base.solutions_pad(n, 4) {
+    let ix = BddBase::decode_onehot(&reg, &group).expect("solution should be one-hot");
+    assert_eq!(reg.hi_bits(), vec![group[ix]]);
+    seen.insert(ix); }
+  assert_eq!(seen, hs(vec![0, 1, 2, 3]));
+  assert_eq!(BddBase::decode_onehot(&Reg::from_bits(4, &[]), &group), None);
+  assert_eq!(BddBase::decode_onehot(&Reg::from_bits(4, &[1, 2]), &group), None); }
+
+#[test] fn test_bdd_walk_deep_chain() {
+  // a recursive walk over a chain this long would overflow the stack.
+  // (the chain is x0 & x1 & ... , pushed straight into the node table, since
+  // going through ite() for each of these nodes would make the test slow.)
+  const N:usize = 100_000;
+  let base = BddBase::new();
+  let mut n = I;
+  for i in 0..N {
+    let ix = base.swarm.hilos().push(HiLo{ hi:n, lo:O });
+    n = NID::from_vid_idx(VID::var(i as u32), ix); }
+  assert_eq!(base.node_count(n), N);
+  let mut order = vec![];
+  base.walk_up(n, &mut |x, _, _, _| order.push(x));
+  assert_eq!(order.len(), N);
+  assert_eq!(order[N-1], n, "walk_up should visit the root last"); }
+
+#[test] fn test_bdd_conjoin_sorted() {
+  let mut base = BddBase::new();
+  // x[k] == x[k+6] for each k, followed by unit constraints fixing the upper half.
+  // folded in this order, the equalities build up a wide intermediate bdd before
+  // the units collapse it. sorted by support size, the units go first.
+  let x:Vec<NID> = (0..12).map(NID::var).collect();
+  let mut cs = vec![];
+  for k in 0..6 { cs.push(base.xor(x[k], !x[k+6])) }
+  cs.extend_from_slice(&x[6..]);
+  let peak = |base:&mut BddBase, cs:&[NID]| {
+    let (mut res, mut peak) = (I, 0);
+    for &c in cs { res = base.and(res, c); peak = peak.max(base.node_count(res)); }
+    (res, peak) };
+  let (unsorted, unsorted_peak) = peak(&mut base, &cs);
+  let sizes:Vec<usize> = cs.iter().map(|&c| base.support(c).len()).collect();
+  let (_, sorted_peak) = peak(&mut base, &apl::at(&cs, &apl::gradeup(&sizes)));
+  assert_eq!(base.conjoin_sorted(&cs), unsorted);
+  assert!(sorted_peak <= unsorted_peak, "sorted peak {} > unsorted peak {}", sorted_peak, unsorted_peak);
+  assert!(sorted_peak < unsorted_peak, "expected the ordering to matter here"); }
+
+#[test] fn test_bdd_first_solution_reg() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let a = base.and(x1, !x2);
+  let b = base.xor(x0, x3);
+  let n = base.and(a, b);
+  assert_eq!(base.first_solution_reg(O, 4), None);
+  assert_eq!(base.first_solution_reg(I, 4), Some(Reg::new(4)));
+  let reg = base.first_solution_reg(n, 4).expect("n should be satisfiable");
+  // plug the solution back in:
+  let mut res = n;
+  for i in 0..4 {
+    let v = VID::var(i as u32);
+    res = if reg.get(i) { base.when_hi(v, res) } else { base.when_lo(v, res) }}
+  assert_eq!(res, I);
+  assert_eq!(Some(reg), base.solutions_pad(n, 4).next(), "should match the first solution from the iterator"); }
+
+#[test] fn test_bdd_apply_fun() {
+  use crate::ops;
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let a = base.and(x0, x2);
+  let b = base.or(x1, !x3);
+  let c = base.xor(x2, x3);
+  let xor = NID::fun(2, 0b0110);
+  let expect = base.xor(a, b);
+  assert_eq!(base.apply_fun(xor, &[a, b]), expect);
+  let expect = base.and(a, b);
+  assert_eq!(base.apply_fun(ops::NAND, &[a, b]), !expect);
+  let expect = base.or(!a, b);
+  assert_eq!(base.apply_fun(ops::IMP, &[a, b]), expect);
+  let expect = base.ite(a, b, c);
+  assert_eq!(base.apply_fun(ops::ITE, &[a, b, c]), expect);
+  // constant inputs get folded into the table:
+  assert_eq!(base.apply_fun(ops::ITE, &[I, b, c]), b);
+  assert_eq!(base.apply_fun(ops::AND, &[O, b]), O); }
+
+#[test] fn test_bdd_sift() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3, x4, x5];
+  // (x0 & x3) | (x1 & x4) | (x2 & x5) is exponential in this order, but linear
+  // when the pairs are adjacent.
+  let a = base.and(x0, x3); let b = base.and(x1, x4); let c = base.and(x2, x5);
+  let ab = base.or(a, b);
+  let n = base.or(ab, c);
+  let before = base.node_count(n);
+  let order = base.sift(&[n]);
+  assert_eq!(order, base.sift(&[n]), "sifting should be deterministic");
+  let slots:Vec<VID> = (0..6).map(VID::var).collect();
+  let perm:HashMap<VID,VID> = order.iter().cloned().zip(slots.iter().cloned()).collect();
+  let m = base.permute_inputs(n, &perm);
+  let after = base.node_count(m);
+  assert!(after < before, "expected sifting to shrink the bdd ({} -> {})", before, after);
+  assert_eq!(after, 6, "expected the optimal (pairs adjacent) order");
+  assert_eq!(base.solution_count(m, 6), base.solution_count(n, 6)); }
+
+#[test] fn test_bdd_swap() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  // n = (x0 & !x1) | x2
+  let a = base.and(x0, !x1); let n = base.or(a, x2);
+  let s = base.swap(n, x0.vid(), x1.vid());
+  let b = base.and(x1, !x0); let e = base.or(b, x2);
+  assert_eq!(s, e);
+  let s = base.swap(n, x0.vid(), x2.vid());
+  let b = base.and(x2, !x1); let e = base.or(b, x0);
+  assert_eq!(s, e); }
+
+#[test] fn test_bdd_tt_for_vars() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x3, x4, x5];
+  let and2 = base.and(x0, x1);
+  let and34 = base.and(x3, x4);
+  assert_eq!(base.tt_for_vars(and34, &[x3.vid(), x4.vid()]), base.tt(and2, 2));
+  assert_eq!(base.tt_for_vars(and34, &[x3.vid(), x4.vid()]), vec![0,0,0,1]);
+  // gaps and reorderings:
+  let n = base.and(x3, !x5);
+  assert_eq!(base.tt_for_vars(n, &[x3.vid(), x5.vid()]), vec![0,1,0,0]);
+  assert_eq!(base.tt_for_vars(n, &[x5.vid(), x3.vid()]), vec![0,0,1,0]);
+  assert_eq!(base.tt_for_vars(n, &[x3.vid(), x4.vid(), x5.vid()]), vec![0,1,0,1,0,0,0,0]); }
+
+#[test] fn test_bdd_from_tt() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let vars:Vec<VID> = (0..4).map(VID::var).collect();
+  let a = base.and(x0, !x2); let b = base.xor(x1, x3);
+  let n = base.or(a, b);
+  let tt = base.tt(n, 4);
+  assert_eq!(base.from_tt(&tt, &vars), n);
+  assert_eq!(base.from_tt(&[0,0,0,1], &[x2.vid(), x3.vid()]), base.and(x2, x3));
+  assert_eq!(base.from_tt(&[1], &[]), I);
+  assert_eq!(base.from_tt(&[0,0], &[x0.vid()]), O); }
+
+#[test] fn test_bdd_thread_count() {
+  assert_eq!(BddBase::new_with_threads(1).thread_count(), 1);
+  assert_eq!(BddBase::new_with_threads(3).thread_count(), 3); }
+
+#[test] fn test_bdd_equiv() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  // de morgan: a & b == !(!a | !b)
+  let a = base.or(x0, x1); let b = base.xor(x1, x2);
+  let lhs = base.and(a, b);
+  let rhs = base.or(!a, !b); let rhs = !rhs;
+  assert!(base.equiv(lhs, rhs));
+  assert!(!base.equiv(lhs, a));
+  // reorder the inputs, then put them back:
+  let s = base.swap(lhs, x0.vid(), x2.vid());
+  assert!(!base.equiv(lhs, s), "swapping x0 and x2 should change this function");
+  let s = base.swap(s, x0.vid(), x2.vid());
+  assert!(base.equiv(lhs, s));
+  let perm:HashMap<VID,VID> = [(x0.vid(), x1.vid()), (x1.vid(), x2.vid()), (x2.vid(), x0.vid())].into_iter().collect();
+  let inv:HashMap<VID,VID> = perm.iter().map(|(&k, &v)| (v, k)).collect();
+  let p = base.permute_inputs(lhs, &perm);
+  let p = base.permute_inputs(p, &inv);
+  assert!(base.equiv(lhs, p)); }
+
+#[test] fn test_bdd_save_load() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3, x4];
+  let a = base.and(x0, x3);
+  let b = base.xor(x1, x4);
+  let c = base.ite(x2, a, b);
+  let n = base.or(c, !x4);
+  base.tag(n, "n".to_string());
+  let path = std::env::temp_dir().join("bex-test-bdd-roundtrip.bdd");
+  let path = path.to_str().unwrap();
+  base.save(path).expect("failed to save");
+  let mut loaded = BddBase::load(path).expect("failed to load");
+  let m = loaded.get("n").expect("tag should survive the round trip");
+  assert_eq!(m, n);
+  assert_eq!(loaded.node_count(m), base.node_count(n));
+  assert_eq!(loaded.tt(m, 5), base.tt(n, 5));
+  // the reloaded base should keep working as normal:
+  let d = loaded.and(x0, x3);
+  assert_eq!(d, a, "rebuilding an existing node should find it in the loaded table"); }
+
+#[test] fn test_bdd_gc() {
+  let mut base = BddBase::new();
+  let x:Vec<NID> = (0..6).map(NID::var).collect();
+  let a = base.and(x[0], x[3]);
+  let b = base.xor(x[1], x[4]);
+  let keep = base.ite(x[5], a, !b); // x5 is topmost, so a and b are subgraphs
+  base.tag(keep, "keep".to_string());
+  // some garbage:
+  for i in 0..5 { let g = base.xor(x[i], x[i+1]); base.tag(g, format!("g{}", i)); let _ = base.and(g, keep); }
+  let tt = base.tt(keep, 6);
+  let before = base.len();
+  let map = base.gc(&[!keep]);
+  assert!(base.len() < before, "gc should drop nodes ({} -> {})", before, base.len());
+  let (kept, after) = (map[&!keep], base.len());
+  assert_eq!(base.tt(!kept, 6), tt);
+  assert_eq!(base.get("keep"), Some(!kept));
+  assert_eq!(base.get("g0"), None, "tags for collected nodes should be dropped");
+  // the old nids are stale now, but rebuilding from the inputs should find the kept nodes:
+  let a = base.and(x[0], x[3]);
+  let b = base.xor(x[1], x[4]);
+  let again = base.ite(x[5], a, !b);
+  assert_eq!(again, !kept);
+  assert_eq!(base.len(), after, "rebuilding shouldn't have added any nodes"); }
+
+#[test] fn test_bdd_gc_shared_hilo() {
+  // and(x2,x0) and and(x1,x0) are different nodes with the same hi/lo pair (x0, O),
+  // so after gc they should still share a single slot in the table.
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let p = base.and(x2, x0);
+  let q = base.and(x1, x0);
+  let _garbage = base.xor(x2, x1);
+  let map = base.gc(&[p, q]);
+  let (p1, q1) = (map[&p], map[&q]);
+  assert_eq!(base.len(), 1);
+  assert_eq!(base.and(x2, x0), p1, "rebuilding should give the remapped nid");
+  assert_eq!(base.and(x1, x0), q1);
+  assert_eq!(base.len(), 1, "rebuilding shouldn't have added any nodes"); }
+
+#[test] fn test_bdd_dot_many() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let ab = base.and(x0, x1);
+  let abc = base.or(ab, x2);
+  let mut s = String::new();
+  base.dot_many(&[("ab".to_string(), ab), ("not abc".to_string(), !abc)], &mut s);
+  assert!(s.contains("root0[label=\"ab\""));
+  assert!(s.contains("root1[label=\"not abc\""));
+  assert!(s.contains(&format!("root1->\"{}\"[arrowhead=dot]", abc)));
+  // ab is reachable from both roots, but should only be declared once:
+  let decl = format!("  \"{}\"[label=", ab);
+  assert_eq!(s.matches(&decl).count(), 1);
+  for n in [abc, ab] { assert!(s.contains(&format!("  \"{}\"[label=", n))) }
+  // quotes and backslashes in the names have to be escaped:
+  let mut s = String::new();
+  base.dot_many(&[("say \"hi\"".to_string(), ab), ("a\\b".to_string(), abc)], &mut s);
+  assert!(s.contains(r#"root0[label="say \"hi\"""#), "{}", s);
+  assert!(s.contains(r#"root1[label="a\\b""#), "{}", s); }
+
+#[test] fn test_bdd_shared_node_count() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let ab = base.and(x0, x1);
+  let abc = base.and(x2, ab); // x2 is on top, so ab is a subgraph of abc
+  let (n0, n1) = (base.node_count(ab), base.node_count(abc));
+  let shared = base.shared_node_count(&[ab, abc]);
+  assert!(shared < n0 + n1, "shared count {} should be less than {} + {}", shared, n0, n1);
+  assert_eq!(shared, n1, "everything under ab is already counted under abc");
+  assert_eq!(base.shared_node_count(&[ab]), n0);
+  assert_eq!(base.shared_node_count(&[]), 0);
+  // a node and its inverse are the same node:
+  let f = base.xor(abc, x1);
+  assert_eq!(base.shared_node_count(&[f, !f]), base.shared_node_count(&[f]));
+  assert_eq!(base.shared_node_count(&[!f]), base.node_count(f)); }
+
+#[test] fn test_bdd_restrict() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let n = base.ite(x0, x1, x2);
+  assert_eq!(base.restrict(n, &HashMap::from([(x0.vid(), true)])), x1);
+  assert_eq!(base.restrict(n, &HashMap::from([(x0.vid(), false)])), x2);
+  assert_eq!(base.restrict(n, &HashMap::new()), n);
+  // compare against chained when_hi/when_lo on something bigger:
+  let a = base.xor(x1, x3);
+  let m = base.ite(a, n, !x3);
+  let fix = HashMap::from([(x3.vid(), true), (x0.vid(), false)]);
+  let expect = { let t = base.when_hi(x3.vid(), m); base.when_lo(x0.vid(), t) };
+  assert_eq!(base.restrict(m, &fix), expect);
+  assert_eq!(base.restrict(!m, &fix), !expect); }
+
+#[test] fn test_bdd_path_count() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1];
+  let x = base.xor(x0, x1);
+  assert_eq!(base.path_count(x), 2);
+  assert_eq!(base.solution_count(x, 2), 2);
+  let inner = base.ite(x1, I, O);
+  let n = base.ite(x0, I, inner);
+  assert_eq!(base.path_count(n), 2);
+  assert_eq!(base.solution_count(n, 2), 3, "the x1=1 path covers both values of x0");
+  assert_eq!(base.path_count(!n), 1);
+  assert_eq!(base.path_count(I), 1);
+  assert_eq!(base.path_count(O), 0); }
+
+#[test] fn test_bdd_cube() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let a = base.and(x0, !x1); let expect = base.and(a, x2);
+  assert_eq!(base.cube(&[x0, !x1, x2]), expect);
+  assert_eq!(base.cube(&[x2, x0, !x1, x0]), expect, "order and duplicates shouldn't matter");
+  assert_eq!(base.cube(&[x0, !x0]), O);
+  assert_eq!(base.cube(&[x1, x0, !x1]), O);
+  assert_eq!(base.cube(&[]), I);
+  assert_eq!(base.cube(&[!x1]), !x1); }
+
+#[test] fn test_bdd_clause() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let a = base.or(x0, !x1); let expect = base.or(a, x2);
+  assert_eq!(base.clause(&[x0, !x1, x2]), expect);
+  assert_eq!(base.clause(&[x2, x0, !x1, x0]), expect, "order and duplicates shouldn't matter");
+  assert_eq!(base.clause(&[x0, !x0]), I);
+  assert_eq!(base.clause(&[x1, x0, !x1]), I);
+  assert_eq!(base.clause(&[]), O);
+  assert_eq!(base.clause(&[!x1]), !x1);
+  let c = base.cube(&[!x0, x1, !x2]);
+  assert_eq!(!c, expect, "a clause is the complement of the cube of the negated literals"); }
+
+#[test] fn test_bdd_symmetric_vars() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let (v0, v1, v2, v3) = (x0.vid(), x1.vid(), x2.vid(), x3.vid());
+  let n = expr![base, ((x0 ^ x1) ^ x2)];
+  assert_eq!(base.symmetric_vars(n, &[v0, v1, v2]), vec![(v0,v1), (v0,v2), (v1,v2)]);
+  assert_eq!(base.symmetric_vars(n, &[v0, v3]), vec![], "x3 isn't in the support");
+  let n = expr![base, ((x0 & x1) ^ x2)];
+  assert_eq!(base.symmetric_vars(n, &[v0, v1, v2, v3]), vec![(v0,v1)]); }
+
+#[test] fn test_bdd_dot_styled() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1];
+  let n = base.and(x0, x1);
+  let style = DotStyle { bgcolor: "white".to_string(), terminal_shape: "box".to_string(), ..DotStyle::default() };
+  let mut s = String::new(); base.dot_styled(n, &style, &mut s);
+  assert!(s.contains("bgcolor=\"white\""));
+  assert!(s.contains("I[label=⊤, shape=box"));
+  let mut d = String::new(); base.dot(n, &mut d);
+  assert!(d.contains("bgcolor=\"#3399cc\""), "dot() should still use the default style"); }
+
+#[test] fn test_bdd_dot_ranked() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let n = expr![base, ((x0 ^ x1) & x2)];
+  let mut s = String::new(); base.dot(n, &mut s);
+  let ranks:Vec<&str> = s.lines().filter(|l| l.contains("rank=same")).collect();
+  assert_eq!(ranks, vec![
+    format!("  {{rank=same \"{}\"}}", n).as_str(),
+    format!("  {{rank=same \"{}\"}}", base.when_hi(x2.vid(), n)).as_str(),
+    format!("  {{rank=same \"{}\" \"{}\"}}", x0, !x0).as_str(),
+    "  {rank=same I O}"], "one rank per variable (top first), then the terminals:\n{}", s); }
+
+#[test] fn test_bdd_cache_limit() {
+  fn build(base:&mut BddBase)->NID {
+    let xs:Vec<NID> = (0..6).map(NID::var).collect();
+    let mut n = O;
+    for i in 0..6 { for j in i+1..6 {
+      let t = base.and(xs[i], !xs[j]);
+      n = base.xor(n, t); }}
+    let at2 = base.atleast(&xs, 2);
+    base.or(n, at2) }
+  let mut limited = BddBase::new(); limited.set_cache_limit(Some(4));
+  let mut plain = BddBase::new();
+  let (a, b) = (build(&mut limited), build(&mut plain));
+  assert_eq!(limited.tt(a, 6), plain.tt(b, 6));
+  // running the same ops again should find the same nodes, even with the cache evicted:
+  assert_eq!(build(&mut limited), a);
+  limited.set_cache_limit(None);
+  assert_eq!(build(&mut limited), a); }
+
+#[test] fn test_bdd_stats() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let a = base.ite(x0, x1, x2);
+  let b = base.ite(x3, a, !x1);
+  base.ite(a, b, x0);
+  let stats = base.stats();
+  assert!(stats.tests >= stats.hits, "{}", stats);
+  assert!(stats.node_count > 0, "{}", stats);
+  assert!(stats.memo_entries > 0, "{}", stats);
+  assert!(format!("{}", stats).contains(&format!("{} nodes", stats.node_count))); }
+
+#[test] fn test_bdd_move_var_to_top() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let (v0, v1, v2, v3) = (x0.vid(), x1.vid(), x2.vid(), x3.vid());
+  let n = expr![base, ((x0 & x1) ^ (x2 & x3))];
+  let n = base.or(n, x1);
+  let m = base.move_var_to_top(n, v1);
+  // the top slot (x3) now plays x1's role, so its cofactors are n's cofactors on x1,
+  // with x2 and x3 shifted down into the slots for x1 and x2:
+  assert_eq!(m.vid(), v3);
+  let (hi, lo) = (base.when_hi(v1, n), base.when_lo(v1, n));
+  let perm:HashMap<VID,VID> = [(v2,v1), (v3,v2)].into_iter().collect();
+  let (phi, plo) = (base.permute_inputs(hi, &perm), base.permute_inputs(lo, &perm));
+  assert_eq!(base.get_vhl(m), (v3, phi, plo));
+  // and relabeling the slots back to their old roles gives the original function:
+  let undo:HashMap<VID,VID> = [(v3,v1), (v2,v3), (v1,v2)].into_iter().collect();
+  let back = base.permute_inputs(m, &undo);
+  assert!(base.equiv(back, n));
+  assert_eq!(base.move_var_to_top(n, v3), n, "x3 is already on top");
+  let k = base.and(x1, x2);
+  assert_eq!(base.move_var_to_top(k, v0), k, "x0 isn't in the support"); }
+
+#[test] fn test_bdd_reorder() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let a = base.and(x0, x1);
+  let f = base.xor(a, x2);
+  let vids = [x2.vid(), x0.vid(), x1.vid()];
+  let res = base.reorder(&vids, &[f, x1, I], false);
+  let perm:HashMap<VID,VID> = vids.iter().enumerate().map(|(i, &v)| (v, VID::var(i as u32))).collect();
+  assert_eq!(res[0], base.permute_inputs(f, &perm));
+  assert_eq!(res[1], x2);
+  assert_eq!(res[2], I);
+  let (tt, before) = (base.tt(res[0], 3), base.len());
+  // with gc, only the results survive, but they still mean the same thing:
+  let res = base.reorder(&vids, &[f], true);
+  assert!(base.len() < before, "gc should drop the nodes for the original f");
+  assert_eq!(base.tt(res[0], 3), tt); }
+
+#[test] fn test_bdd_try_reorder_errors() {
+  use crate::vid::ReorderError::*;
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2];
+  let f = base.and(x0, x2);
+  let (v0, v1, v2) = (x0.vid(), x1.vid(), x2.vid());
+  assert_eq!(base.try_reorder(&[v0, v2], &[f], false), Err(Gap(v1)));
+  assert_eq!(base.try_reorder(&[v0, v1, v0], &[f], false), Err(Duplicate(v0)));
+  assert_eq!(base.try_reorder(&[v0, VID::vir(1)], &[f], false), Err(NotVar(VID::vir(1))));
+  assert_eq!(base.try_reorder(&[v1, v0], &[f], false), Err(Missing(v2)));
+  assert_eq!(base.try_reorder(&[v1, v0], &[x0], false), Ok(vec![x1])); }
+
+#[test] #[should_panic] fn test_bdd_reorder_panics() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1];
+  base.reorder(&[x1.vid()], &[x0], false); }
+
+#[test] fn test_bdd_satisfy_all() {
+  let mut base = BddBase::new();
+  nid_vars![x0, x1, x2, x3];
+  let n = expr![base, ((x0 ^ x2) & (x1 ^ x3))];
+  let n = base.or(n, x3);
+  let sols:Vec<Reg> = base.satisfy_all(n, 5).collect();
+  let ixs:Vec<usize> = sols.iter().map(|r| r.as_usize()).collect();
+  let mut sorted = ixs.clone(); sorted.sort();
+  assert_eq!(ixs, sorted, "solutions should come out in order");
+  assert_eq!(sols.iter().cloned().collect::<HashSet<Reg>>(), base.solution_set(n, 5));
+  let smallest = (0..32).find(|&i| base.tt(n, 5)[i] == 1).unwrap();
+  assert_eq!(ixs[0], smallest);
+  assert_eq!(base.satisfy_all(O, 3).count(), 0);
+  assert_eq!(base.satisfy_all(n, 5).take(2).count(), 2, "it's lazy, so we can stop early"); }
+
+#[test] fn test_bdd_solutions_skipped_between() {
+  // x1 is skipped between the two branches, and the first leaf after a
+  // solution is a dead end. (This used to miss the second solution.)
+  let mut base = BddBase::new();
+  let (x0, x2) = (NID::var(0), NID::var(2));
+  let n = base.and(x2, !x0);
+  let v:Vec<usize> = base.solutions_pad(n, 3).map(|r| r.as_usize()).collect();
+  assert_eq!(v, vec![0b100, 0b110]);
+  let n = base.and(x2, x0);
+  let v:Vec<usize> = base.solutions_pad(n, 4).map(|r| r.as_usize()).collect();
+  assert_eq!(v, vec![0b0101, 0b0111, 0b1101, 0b1111]); }
+
+#[test] fn test_bdd_cursor_seek() {
+  use crate::cur::Cursor;
+  let mut base = BddBase::new();
+  nid_vars![x0, x1];
+  let n = base.or(x0, x1);
+  let all:Vec<usize> = base.solutions_pad(n, 3).map(|r| r.as_usize()).collect();
+  assert_eq!(all, vec![0b001, 0b010, 0b011, 0b101, 0b110, 0b111]);
+  let from = |base:&BddBase, start:usize|->Vec<usize> {
+    let mut cur = Cursor::new(3, n);
+    let mut res = vec![];
+    if cur.seek(base, &Reg::from_u128(start as u128, 3)) {
+      let mut next = Some(cur);
+      while let Some(c) = next { res.push(c.scope.as_usize()); next = base.next_solution(c) }}
+    res };
+  for start in 0..8 {
+    let expect:Vec<usize> = all.iter().copied().filter(|&x| x >= start).collect();
+    assert_eq!(from(&base, start), expect, "seeking to {:03b}", start); }
+  // each seek into 0b100 lands on the next solution, 0b101:
+  assert_eq!(from(&base, 0b100)[0], 0b101);
+  // and a bdd with no solutions at or after the target:
+  let m = base.and(x0, !x1);
+  let mut cur = Cursor::new(3, m);
+  assert!(!cur.seek(&base, &Reg::from_u128(0b110, 3)));
+  assert!(cur.seek(&base, &Reg::from_u128(0b010, 3)));
+  assert_eq!(cur.scope.as_usize(), 0b101); }
@@ -26,6 +26,112 @@ fn check_swap(old:&str, new:&str) {
   check_swap("a abv? w?", "aabw? v? ");
   check_swap("abv? b w?", "abw? bv? "); }
 
+#[test] fn test_scaffold_node_count() {
+  // a b c d, plus the two v nodes and w on top:
+  let mut xsd = XSDebug::new("abcdvw");
+  let (v, x) = (xsd.vid('v'), xsd.xid("abv? cdv? w?"));
+  assert_eq!(xsd.xs.node_count(x), 7);
+  xsd.xs.swap(v); // -> "acw? bdw? v?"
+  assert_eq!(xsd.xs.node_count(x), 7);
+  // here the swap lets two nodes merge:
+  let mut xsd = XSDebug::new("abcdvw");
+  let (v, x) = (xsd.vid('v'), xsd.xid("abv? acv? w?"));
+  assert_eq!(xsd.xs.node_count(x), 6);
+  xsd.xs.swap(v); // -> "abcw? v?"
+  assert_eq!(xsd.xs.node_count(x), 5);
+  assert_eq!(xsd.xs.node_count(XID_O), 0); }
+
+#[test] fn test_scaffold_dot() {
+  let mut xsd = XSDebug::new("abcdvw");
+  let small = xsd.xid("abv?");
+  let top = xsd.xid("abv? cdv? w?");
+  let (mut full, mut part, mut whole) = (String::new(), String::new(), String::new());
+  xsd.xs.write_dot(O, &mut full);
+  xsd.xs.write_dot(small.to_nid(), &mut part);
+  xsd.xs.write_dot(top.to_nid(), &mut whole);
+  assert!(part.lines().count() < full.lines().count(), "dot for one node should be smaller than the whole scaffold");
+  assert!(part.contains(&format!("\"{:?}\"", small)));
+  assert!(!part.contains(&format!("\"{:?}\"", top)), "top isn't reachable from small");
+  assert!(whole.contains(&format!("\"{:?}\"", small))); }
+
+#[cfg(test)]
+fn regroup_fixture()->(XSDebug, XID, Vec<HashSet<VID>>) {
+  let mut xsd = XSDebug::new("abcdefg");
+  let top = xsd.xid("abc? bad? e? cdf? g?");
+  let vs = |s:&str| s.chars().map(|c| xsd.vid(c)).collect::<HashSet<VID>>();
+  // lift a, c, and e to the top, with g in between:
+  let groups = vec![vs("bdf"), vs("g"), vs("ace")];
+  (xsd, top, groups) }
+
+#[test] fn test_regroup_cancel() {
+  use std::sync::atomic::AtomicBool;
+  let done = |xsd:&XSDebug| {
+    let mut bottom:Vec<char> = xsd.vids()[..3].chars().collect(); bottom.sort();
+    bottom == ['b','d','f'] && xsd.vids()[3..4] == *"g" };
+  // if the flag is already set, nothing should move.
+  let (mut xsd, top, groups) = regroup_fixture();
+  let before = xsd.fmt(top);
+  let cancel = AtomicBool::new(true);
+  assert_eq!(xsd.xs.regroup_cancellable(groups, &cancel), Err(Cancelled));
+  xsd.xs.validate("after cancel");
+  assert_eq!(xsd.vids(), "abcdefg");
+  assert_eq!(xsd.fmt(top), before);
+  // cancelling partway through should leave the scaffold valid, even though
+  // the regroup didn't finish.
+  let (mut xsd, top, groups) = regroup_fixture();
+  let mut swapped = 0;
+  let res = xsd.xs.regroup_aux(groups, &mut |swaps| { swapped = swaps; swaps >= 1 });
+  assert_eq!(res, Err(Cancelled));
+  xsd.xs.validate("after partial regroup");
+  assert!(swapped >= 1, "expected at least one swap to happen");
+  assert_ne!(xsd.vids(), "abcdefg", "expected at least one swap to happen");
+  assert!(!done(&xsd), "regroup shouldn't have finished");
+  assert!(xsd.xs.node_count(top) > 0);
+  // and with no cancellation, it should finish:
+  let (mut xsd, _, groups) = regroup_fixture();
+  assert_eq!(xsd.xs.regroup_cancellable(groups, &AtomicBool::new(false)), Ok(()));
+  assert!(done(&xsd), "unexpected order: {}", xsd.vids()); }
+
+#[test] fn test_regroup_cancel_flag_midway() {
+  use std::sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc};
+  // another thread waits for the first swap, then sets the real cancel flag.
+  // (the progress callback waits for it to do so, so the test is deterministic.)
+  let cancel = Arc::new(AtomicBool::new(false));
+  let (swapped_tx, swapped_rx) = mpsc::channel::<()>();
+  let (flagged_tx, flagged_rx) = mpsc::channel::<()>();
+  let flag = cancel.clone();
+  let canceller = std::thread::spawn(move || {
+    swapped_rx.recv().unwrap();
+    flag.store(true, Ordering::Relaxed);
+    flagged_tx.send(()).unwrap(); });
+  let (mut xsd, top, groups) = regroup_fixture();
+  let mut notified = false;
+  let res = xsd.xs.regroup_with_progress(groups, &cancel, &mut |swaps| {
+    if swaps >= 1 && !notified {
+      notified = true;
+      swapped_tx.send(()).unwrap();
+      flagged_rx.recv().unwrap(); }});
+  canceller.join().unwrap();
+  assert!(notified, "expected at least one swap to happen");
+  assert_eq!(res, Err(Cancelled));
+  xsd.xs.validate("after cancelling from another thread");
+  assert_ne!(xsd.vids(), "abcdefg", "expected at least one swap to happen");
+  assert!(xsd.xs.node_count(top) > 0); }
+
+#[test] fn test_scaffold_save_load() {
+  let mut xsd = XSDebug::new("abcdvw");
+  let x = xsd.xid("abv? cdv? w?");
+  let path = std::env::temp_dir().join("bex-test-scaffold.xvhl");
+  let path = path.to_str().unwrap();
+  xsd.xs.save(path).expect("failed to save scaffold");
+  let xs = XVHLScaffold::load(path).expect("failed to load scaffold");
+  xs.validate("after load");
+  assert_eq!(xs.vids, xsd.xs.vids);
+  assert_eq!(xs.get_refcount(x), xsd.xs.get_refcount(x));
+  xsd.xs = xs;
+  assert_eq!(xsd.fmt(x), "abv? cdv? w? ");
+  std::fs::remove_file(path).unwrap(); }
+
 #[test] fn test_tbl() {
   let mut xsd = XSDebug::new("abcd");
   let x = xsd.xid("a 1 b? 0 c?");
@@ -99,7 +205,7 @@ fn check_sub(vids:&str, dst_s:&str, v:char, src_s:&str, goal:&str) {
       2 => src.var(*cv.get(&c).expect("bad entry in src vars"), c),
       3 => {
         let mut parts = vids.split('|');
-        expected_order = (if c=='=' { parts.next() } else { parts.last() }).unwrap();
+        expected_order = (if c=='=' { parts.next() } else { parts.next_back() }).unwrap();
         break },
       _ => panic!("too many '|' chars encountered!") }}}
 
@@ -172,6 +278,24 @@ fn check_sub(vids:&str, dst_s:&str, v:char, src_s:&str, goal:&str) {
   // !! if the final order breaks on this test due to a regroup() change, it's okay: z isn't used.
   check_sub("xyz|xyz|zx|xz", "xyz?", 'y', "z!zx?", "x")}
 
+/// same substitution as test_two_old, but make sure the solutions are still
+/// expressed in terms of all three inputs, even though only x is left.
+#[test] fn test_two_old_get_all() {
+  let mut dst = XSDebug::new("xyz");
+  let dx = dst.xid("xyz?");
+  let rv = dst.vid('y');
+  let mut src = XSDebug::new("");
+  src.var(2, 'z'); src.var(0, 'x');
+  let sx = src.xid("z!zx?");
+  let mut ss = SwapSolver::new(); ss.init(rv);
+  ss.dst = dst.xs; ss.dx = dx;
+  ss.src = src.xs; ss.sx = sx;
+  let ctx = ss.sub().to_nid();
+  let actual:HashSet<usize> = ss.get_all(ctx, 3).iter().map(|r| r.as_usize()).collect();
+  // x is bit 0. y and z are "don't care" bits:
+  let expected:HashSet<usize> = [0b001, 0b011, 0b101, 0b111].into_iter().collect();
+  assert_eq!(actual, expected); }
+
 /// test for subbing in one new variable
 #[test] fn test_one_new() {
   //                                   wy^
@@ -248,3 +372,19 @@ println!("----------");
   // but here, x4 is at the end, and nothing will ever swap with it, so we can drop it from the plan.
   assert_eq!(d!{ x3:3 }, plan_regroup(&[x3,x1,x2,x0,x4], &[s![x2,x0,x1],s![],s![x4,x3]]));
 }
+
+
+// -- SwapSolver solutions ----------------------------------------------------
+
+#[test] fn test_get_one() {
+  use crate::{ast::ASTBase, base::Base, solve::solve};
+  let (x0, x1, x2) = (NID::var(0), NID::var(1), NID::var(2));
+  let mut base = ASTBase::empty();
+  let (a, b) = (base.or(x0, x1), base.xor(x1, x2));
+  let top = base.and(a, b);
+  let mut ss = SwapSolver::new();
+  let ctx = solve(&mut ss, base.raw_ast(), top).n;
+  let all = ss.get_all(ctx, 3);
+  let one = ss.get_one(ctx, 3).expect("expected a solution");
+  assert!(all.contains(&one), "{:?} should be in {:?}", one, all);
+  assert_eq!(ss.get_one(O, 3), None); }
@@ -2,6 +2,7 @@
 use std::collections::BinaryHeap;
 use std::collections::HashSet;
 use dashmap::DashMap;
+use serde::{Serialize, Deserialize};
 use crate::nid::NID;
 use crate::vid::VID;
 
@@ -14,7 +15,7 @@ struct VhlVec<T>{ pub vec: boxcar::Vec<T> }
 /// Simple Hi/Lo pair stored internally when representing nodes.
 /// All nodes with the same branching variable go in the same array, so there's
 /// no point duplicating it.
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct HiLo {pub hi:NID, pub lo:NID}
 
 impl HiLo {
@@ -33,7 +34,7 @@ impl std::ops::Not for HiLo {
 
 
 /// Vhl (for when we really do need the variable)
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Vhl {pub v:VID, pub hi:NID, pub lo:NID}
 
 impl Vhl {
@@ -110,6 +111,24 @@ impl HiLoCache {
 
   pub fn new()->Self { Self::default() }
 
+  /// number of hi/lo pairs in the cache
+  pub fn len(&self)->usize { self.hilos.vec.len() }
+
+  /// true when the cache is empty
+  pub fn is_empty(&self)->bool { self.hilos.vec.is_empty() }
+
+  /// iterate through the hi/lo pairs in index order
+  pub fn iter(&self)->impl Iterator<Item=HiLo> + '_ { self.hilos.vec.iter().cloned() }
+
+  /// copy of the hi/lo pairs, in index order (for serialization)
+  pub fn to_vec(&self)->Vec<HiLo> { self.iter().collect() }
+
+  /// append an already-normalized hi/lo pair and return its index (for deserialization)
+  pub fn push(&self, hilo:HiLo)->usize {
+    let ix = self.hilos.vec.push(hilo);
+    self.index.insert(hilo, ix);
+    ix }
+
   // TODO: ->Option<HiLo>, and then impl HiLoBase
   #[inline] pub fn get_hilo(&self, n:NID)->HiLo {
     assert!(!n.is_lit());
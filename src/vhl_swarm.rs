@@ -11,7 +11,7 @@ use std::sync::mpsc::Sender;
 use std::{fmt, hash::Hash};
 use std::sync::Arc;
 use concurrent_queue::{ConcurrentQueue,PopError};
-use crate::vhl::HiLoPart;
+use crate::vhl::{HiLoPart, HiLoCache};
 use crate::vid::VID;
 use crate::wip::Answer;
 use crate::NID;
@@ -145,7 +145,9 @@ impl<J,H> Worker<VhlQ<J>, R, J> for VhlWorker<J,H> where J:JobKey, H:VhlJobHandl
 pub struct VhlSwarm<J, H> where J:JobKey, H:VhlJobHandler<J,W=VhlWorker<J,H>>{
   swarm: Swarm<VhlQ<J>, R, VhlWorker<J, H>, J>,
   state: Arc<WorkState<J>>,
-  queue: Arc<JobQueue<J>>}
+  queue: Arc<JobQueue<J>>,
+  /// if set, finished entries are evicted from the memo cache once it grows past this size.
+  cache_limit: Option<usize>}
 
 impl<J,H> VhlSwarm<J,H> where J:JobKey, H:VhlJobHandler<J,W=VhlWorker<J,H>> {
 
@@ -163,6 +165,9 @@ impl<J,H> VhlSwarm<J,H> where J:JobKey, H:VhlJobHandler<J,W=VhlWorker<J,H>> {
 
   pub fn q_sender(&self)->Sender<VhlQ<J>> { self.swarm.q_sender() }
 
+  /// number of worker threads in the swarm
+  pub fn num_workers(&self)->usize { self.swarm.num_workers() }
+
   // reset internal state without the cost of destroying and recreating
   // all the worker threads.
   pub fn reset(&mut self) {
@@ -172,6 +177,22 @@ impl<J,H> VhlSwarm<J,H> where J:JobKey, H:VhlJobHandler<J,W=VhlWorker<J,H>> {
 
   pub fn tup(&self, n:NID)->(NID,NID) { self.state.tup(n) }
 
+  /// limit the number of entries in the memo cache (None means no limit).
+  /// The cache is only a speedup, so evicting entries never changes any results.
+  pub fn set_cache_limit(&mut self, n:Option<usize>) { self.cache_limit = n; self.evict() }
+
+  /// number of entries (finished or not) in the memo cache.
+  pub fn cache_len(&self)->usize { self.state.cache.len() }
+
+  /// drop the finished entries from the memo cache if it has grown past the limit.
+  /// (Work in progress is kept, since workers may still be filling it in.)
+  fn evict(&mut self) {
+    if let Some(limit) = self.cache_limit {
+      if self.state.cache.len() > limit { self.state.cache.retain(|_, w| w.is_todo()) }}}
+
+  /// the hi/lo pairs for all nodes in the shared state
+  pub fn hilos(&self)->&HiLoCache { self.state.hilos() }
+
   pub fn run_swarm_job(&mut self, job:J)->NID {
     let mut result: Option<NID> = None;
     self.swarm.add_query(VhlQ::Job(job));
@@ -183,6 +204,7 @@ impl<J,H> VhlSwarm<J,H> where J:JobKey, H:VhlJobHandler<J,W=VhlWorker<J,H>> {
         R::Ret(n) => { result = Some(n) }
         R::CacheStats{ tests:_, hits:_ }
           => { panic!("got R::CacheStats before sending Q::Stats"); } }}}
+    self.evict();
     result.unwrap() }
 
   pub fn get_stats(&mut self) {
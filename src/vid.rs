@@ -1,9 +1,10 @@
 //! Variable IDs (used interally by Base implementations)
 use std::cmp::Ordering;
 use std::fmt;
+use serde::{Serialize, Deserialize};
 
 /// this will probably go away in favor of a bitmask at some point
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Serialize, Deserialize)]
 enum VidEnum {
   // How I (eventually) want the ordering, to be (once biggest vars go on top:)
   T,        // Special meta-constant on which I and O branch.
@@ -21,7 +22,7 @@ pub enum VidOrdering {
 use self::VidEnum::*;
 
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct VID { v:VidEnum }
 pub const NOV:VID = VID::nov();
 pub const TOP:VID = VID::top();
@@ -90,7 +91,61 @@ impl VID {
 
   pub fn bitmask(&self)->u64 { match self.v {
     NoV|T => 0,
-    Var(x) | Vir(x) => if x < 64 { 1 << x as u64 } else { 0 }}}}
+    Var(x) | Vir(x) => if x < 64 { 1 << x as u64 } else { 0 }}}
+
+  /// the real variables with indices in the given range, in order.
+  pub fn range_vars(r:std::ops::Range<u32>)->Vec<VID> { r.map(VID::var).collect() }}
+
+
+/// The ways a list of vids can fail to be a complete variable order.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReorderError {
+  /// the list contains something other than a real variable.
+  NotVar(VID),
+  /// the variable appears more than once.
+  Duplicate(VID),
+  /// the variable is below the topmost one in the list, but doesn't appear in it.
+  Gap(VID),
+  /// the variable is used by one of the nodes being reordered, but isn't in the list.
+  Missing(VID) }
+
+impl fmt::Display for ReorderError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ReorderError::NotVar(v) => write!(f, "{} is not a real variable", v),
+      ReorderError::Duplicate(v) => write!(f, "{} appears more than once", v),
+      ReorderError::Gap(v) => write!(f, "{} is missing (the order has a gap)", v),
+      ReorderError::Missing(v) => write!(f, "{} is in the support, but not in the order", v) }}}
+
+impl std::error::Error for ReorderError {}
+
+/// A list of real variables that has been checked to be a complete permutation
+/// of x0..=xN, where xN is the topmost variable in the list. (That is, no
+/// duplicates and no gaps.) Useful for building a custom variable order.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VidOrder(Vec<VID>);
+
+impl VidOrder {
+  /// check that vids is a complete permutation, or explain why it isn't.
+  pub fn try_new(vids:Vec<VID>)->Result<VidOrder,String> {
+    VidOrder::check(&vids).map_err(|e| e.to_string())?;
+    Ok(VidOrder(vids)) }
+
+  /// the check behind try_new(), with the problem as a ReorderError.
+  pub fn check(vids:&[VID])->Result<(),ReorderError> {
+    let mut seen = std::collections::HashSet::new();
+    for &v in vids {
+      if !v.is_var() { return Err(ReorderError::NotVar(v)) }
+      if !seen.insert(v) { return Err(ReorderError::Duplicate(v)) }}
+    // n distinct vars cover x0..x(n-1) unless one of those is missing:
+    match (0..vids.len() as u32).map(VID::var).find(|v| !seen.contains(v)) {
+      Some(v) => Err(ReorderError::Gap(v)),
+      None => Ok(()) }}
+
+  /// the variables, in the order given to try_new()
+  pub fn vids(&self)->&[VID] { &self.0 }
+  pub fn len(&self)->usize { self.0.len() }
+  pub fn is_empty(&self)->bool { self.0.is_empty() }}
 
 
 /// Pretty-printer for NIDS that reveal some of their internal data.
@@ -126,4 +181,29 @@ impl Ord for VID {
 
 impl PartialOrd for VID {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-      Some(self.cmp(other))}}
\ No newline at end of file
+      Some(self.cmp(other))}}
+
+
+#[test] fn test_range_vars() {
+  assert_eq!(VID::range_vars(0..3), vec![VID::var(0), VID::var(1), VID::var(2)]);
+  assert_eq!(VID::range_vars(2..4), vec![VID::var(2), VID::var(3)]);
+  assert!(VID::range_vars(3..3).is_empty()); }
+
+#[test] fn test_vid_order() {
+  let ord = VidOrder::try_new(vec![VID::var(2), VID::var(0), VID::var(1)]).unwrap();
+  assert_eq!(ord.vids(), &[VID::var(2), VID::var(0), VID::var(1)]);
+  assert!(VidOrder::try_new(VID::range_vars(0..5)).is_ok());
+  assert!(VidOrder::try_new(vec![]).is_ok());
+  // x1 is missing:
+  assert!(VidOrder::try_new(vec![VID::var(0), VID::var(2)]).is_err());
+  assert!(VidOrder::try_new(vec![VID::var(0), VID::var(0)]).is_err());
+  assert!(VidOrder::try_new(vec![VID::var(0), VID::vir(1)]).is_err()); }
+
+#[test] fn test_vid_order_check() {
+  use ReorderError::*;
+  let (x0, x1, x2) = (VID::var(0), VID::var(1), VID::var(2));
+  assert_eq!(VidOrder::check(&[x1, x0, x2]), Ok(()));
+  assert_eq!(VidOrder::check(&[x0, x2]), Err(Gap(x1)));
+  assert_eq!(VidOrder::check(&[x2, x0, x2]), Err(Duplicate(x2)));
+  assert_eq!(VidOrder::check(&[x0, VID::vir(1)]), Err(NotVar(VID::vir(1))));
+  assert_eq!(VidOrder::try_new(vec![x0, x2]), Err("x1 is missing (the order has a gap)".to_string())); }
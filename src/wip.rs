@@ -11,8 +11,8 @@
 //! - [`WipRef`] is really just `Wip<K,P>`.
 //! - [`Wip<K,P>`] has `parts: P` and `deps: Vec<Dep<K>>`.
 //! - [`Dep<K>`] tracks which other queries are dependent on this one. It has
-//!     a `HiLoPart` and an `invert` flag. (TODO: explicit use of invert and
-//!     HiloPart should probably be in a `VhlDep` struct.)
+//!   a `HiLoPart` and an `invert` flag. (TODO: explicit use of invert and
+//!   HiloPart should probably be in a `VhlDep` struct.)
 //!
 //! With this framework, we can track the progress of a distributed computation.
 //!
@@ -128,6 +128,9 @@ impl<K:Eq+Hash+Debug,V:Clone> WorkState<K,V> {
 
   pub fn get_hilo(&self, n:NID)->HiLo { self.hilos.get_hilo(n) }
 
+  /// direct access to the hi/lo cache (for serialization)
+  pub fn hilos(&self)->&HiLoCache { &self.hilos }
+
   /// return (hi, lo) pair for the given nid. used internally
   #[inline] pub fn tup(&self, n:NID)-> (NID, NID) {
     use crate::nid::{I,O};